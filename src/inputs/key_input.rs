@@ -15,6 +15,8 @@ pub struct KeyInputMap {
     pub key_fly: KeyCode,
     pub key_toggleview: KeyCode,
     pub key_escape: KeyCode,
+    pub key_reverser: KeyCode,
+    pub key_exit_train: KeyCode,
 }
 
 impl Default for KeyInputMap {
@@ -30,6 +32,8 @@ impl Default for KeyInputMap {
             key_fly: KeyCode::F,
             key_toggleview: KeyCode::T,
             key_escape: KeyCode::Escape,
+            key_reverser: KeyCode::R,
+            key_exit_train: KeyCode::E,
         }
     }
 }
@@ -45,8 +49,14 @@ pub struct KeyInputState {
     pub up: bool,
     pub down: bool,
     pub jump: bool,
+    pub crouch: bool,
     pub toggle_fly: bool,
     pub toggle_view: bool,
+    pub throttle_up: bool,
+    pub throttle_down: bool,
+    pub brake: bool,
+    pub toggle_reverser: bool,
+    pub exit_train: bool,
 }
 
 // Plugin for keyboard input systems
@@ -82,6 +92,7 @@ pub fn input_to_move(
     state.toggle_fly = cursor_lock.enabled && keyboard_input.just_pressed(input_map.key_fly);
     state.toggle_view = cursor_lock.enabled && keyboard_input.just_pressed(input_map.key_toggleview);
     state.jump = cursor_lock.enabled && keyboard_input.just_pressed(input_map.key_jump);
+    state.crouch = cursor_lock.enabled && keyboard_input.pressed(input_map.key_crouch);
 
     // update desired velocity from key states
     state.forward = cursor_lock.enabled && keyboard_input.pressed(input_map.key_forward);
@@ -90,4 +101,11 @@ pub fn input_to_move(
     state.left = cursor_lock.enabled && keyboard_input.pressed(input_map.key_left);
     state.up = cursor_lock.enabled && keyboard_input.pressed(input_map.key_jump);
     state.down = cursor_lock.enabled && keyboard_input.pressed(input_map.key_crouch);
+
+    // update train controls from key states
+    state.throttle_up = cursor_lock.enabled && keyboard_input.just_pressed(input_map.key_forward);
+    state.throttle_down = cursor_lock.enabled && keyboard_input.just_pressed(input_map.key_backward);
+    state.brake = cursor_lock.enabled && keyboard_input.pressed(input_map.key_jump);
+    state.toggle_reverser = cursor_lock.enabled && keyboard_input.just_pressed(input_map.key_reverser);
+    state.exit_train = cursor_lock.enabled && keyboard_input.just_pressed(input_map.key_exit_train);
 }
@@ -2,6 +2,7 @@ use bevy::{prelude::*, input::mouse::{MouseMotion,MouseWheel}};
 #[cfg(target_arch = "wasm32")]
 use web_sys;
 use crate::game_state::GameState;
+use crate::settings::SettingsAsset;
 
 pub struct MouseSettings {
     pub sensitivity: f32,
@@ -141,6 +142,7 @@ pub fn input_to_look(
     mut mouse_wheel_events: EventReader<MouseWheel>,
     mut mouse_look: ResMut<MouseLookState>,
     settings: Res<MouseSettings>,
+    game_settings: Res<SettingsAsset>,
     cursor_lock: Res<CursorLockState>,
 ) {
     let mut delta = Vec2::ZERO;
@@ -180,5 +182,7 @@ pub fn input_to_look(
         mouse_look.right = rotation * Vec3::X;
         mouse_look.up = rotation * Vec3::Y;
     }
-    mouse_look.zoom += wheel_delta_y * settings.zoom_sensitivity;
+    let camera_settings = &game_settings.camera_settings;
+    mouse_look.zoom = (mouse_look.zoom + wheel_delta_y * settings.zoom_sensitivity)
+        .clamp(camera_settings.min_zoom, camera_settings.max_zoom.max(camera_settings.min_zoom));
 }
@@ -13,7 +13,7 @@ use crate::inputs::{KeyInputPlugin, MouseInputPlugin};
 use crate::loading::{AssetLoadingPlugin,LoadingUiStatePlugin,PreLoadingPlugin,
     World01LoadingPlugin,World03LoadingPlugin};
 use crate::menu::MenuPlugin;
-use crate::movement::{CharacterLoadingPlugin,MovementStatePlugin};
+use crate::movement::{AnimationGraphLoaderPlugin,CharacterLoadingPlugin,MovementStatePlugin};
 use crate::settings::SettingsPlugin;
 use crate::world::{WorldAssetLoaderPlugin,WorldLoadingPlugin,WorldStatePlugin};
 
@@ -35,6 +35,7 @@ impl Plugin for GamePlugin {
             .add_plugin(MenuPlugin)
             .add_plugin(DiagOverlayPlugin)
             .add_plugin(WorldAssetLoaderPlugin)
+            .add_plugin(AnimationGraphLoaderPlugin)
             .add_plugin(WorldLoadingPlugin)
             .add_plugin(KeyInputPlugin)
             .add_plugin(MouseInputPlugin)
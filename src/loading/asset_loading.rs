@@ -1,4 +1,5 @@
-use crate::{game_state::GameState, settings::SettingsAsset, world::WorldAsset};
+use crate::{game_state::GameState, movement::AnimationGraphAsset, settings::SettingsAsset, world::WorldAsset};
+use bevy::gltf::Gltf;
 use bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
 use bevy_kira_audio::AudioSource;
@@ -39,14 +40,39 @@ pub struct AudioAssets {
     pub big_switch: Handle<AudioSource>,
     #[asset(path = "audio/flying.ogg")]
     pub flying: Handle<AudioSource>,
+    #[asset(path = "audio/steps_concrete.ogg")]
+    pub steps_concrete: Handle<AudioSource>,
+    #[asset(path = "audio/steps_metal_grating.ogg")]
+    pub steps_metal_grating: Handle<AudioSource>,
     #[asset(path = "audio/steps_snow_dry.ogg")]
     pub steps_snow_dry: Handle<AudioSource>,
+    #[asset(path = "audio/steps_wood.ogg")]
+    pub steps_wood: Handle<AudioSource>,
+}
+
+impl AudioAssets {
+    // preloaded sound by name, others load from audio/ when first played
+    pub fn sound(&self, name: &str) -> Option<Handle<AudioSource>> {
+        match name {
+            "big_switch" => Some(self.big_switch.clone()),
+            "flying" => Some(self.flying.clone()),
+            "steps_concrete" => Some(self.steps_concrete.clone()),
+            "steps_metal_grating" => Some(self.steps_metal_grating.clone()),
+            "steps_snow_dry" => Some(self.steps_snow_dry.clone()),
+            "steps_wood" => Some(self.steps_wood.clone()),
+            _ => None,
+        }
+    }
 }
 
 #[derive(AssetCollection)]
 pub struct CharacterAssets {
     #[asset(path = "characters/graham_v4.glb#Scene0")]
     pub graham: Handle<Scene>,
+    #[asset(path = "characters/graham_v4.glb")]
+    pub graham_gltf: Handle<Gltf>,
+    #[asset(path = "characters/graham.animgraph")]
+    pub graham_animation_graph: Handle<AnimationGraphAsset>,
 }
 
 #[derive(AssetCollection)]
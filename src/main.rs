@@ -1,7 +1,7 @@
 // disable console on windows for release builds
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use bevy::prelude::{App, ClearColor, Color, Msaa, WindowDescriptor};
+use bevy::prelude::{App, Msaa, WindowDescriptor};
 use bevy::DefaultPlugins;
 use bevy_kira_audio::prelude::*;
 use bevy_rapier3d::prelude::*;
@@ -11,7 +11,6 @@ use grahams_relay::GamePlugin;
 fn main() {
     App::new()
         .insert_resource(Msaa { samples: 1 })
-        .insert_resource(ClearColor(Color::rgb(0.2, 0.2, 0.2)))
         .insert_resource(WindowDescriptor {
             width: 1280.,
             height: 720.,
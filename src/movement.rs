@@ -1,6 +1,14 @@
+mod animation_graph_loader;
+pub use animation_graph_loader::*;
+mod animation_state;
+pub use animation_state::*;
+mod character_controller;
+pub use character_controller::*;
 mod character_loading;
 pub use character_loading::*;
 mod character_state;
 pub use character_state::*;
 mod movement_state;
 pub use movement_state::*;
+mod traversal_state;
+pub use traversal_state::*;
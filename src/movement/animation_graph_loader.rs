@@ -0,0 +1,84 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+// character animation states, and the transitions between them in order of priority
+#[derive(Debug, Deserialize, TypeUuid)]
+#[uuid = "39cadc56-aa9c-4543-8640-a008b74b4a17"]
+pub struct AnimationGraphAsset {
+    pub initial: String,
+    pub states: HashMap<String, AnimationGraphState>,
+    pub transitions: Vec<AnimationGraphTransition>,
+}
+
+// clip by its gltf animation name
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct AnimationGraphState {
+    pub clip: String,
+    pub speed: f32,
+    pub repeat: bool,
+    // steps are heard on the beat of the clip
+    pub footsteps: bool,
+}
+
+impl Default for AnimationGraphState {
+    fn default() -> Self {
+        Self {
+            clip: String::new(),
+            speed: 1.0,
+            repeat: true,
+            footsteps: false,
+        }
+    }
+}
+
+// taken from any of the from states, or from anywhere when empty, once all conditions hold
+// conditions are interacting, traversing, grounded, moving, running or crouched, negated by a leading !
+#[derive(Clone, Debug, Deserialize)]
+pub struct AnimationGraphTransition {
+    #[serde(default)]
+    pub from: Vec<String>,
+    pub to: String,
+    #[serde(default)]
+    pub conditions: Vec<String>,
+    // seconds of crossfade
+    #[serde(default)]
+    pub fade: f32,
+}
+
+#[derive(Default)]
+pub struct AnimationGraphLoader;
+
+impl AssetLoader for AnimationGraphLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let animation_graph = ron::de::from_bytes::<AnimationGraphAsset>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(animation_graph));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["animgraph"]
+    }
+}
+
+pub struct AnimationGraphLoaderPlugin;
+
+impl Plugin for AnimationGraphLoaderPlugin {
+    fn build(&self, app: &mut App) {
+        app
+            .add_asset::<AnimationGraphAsset>()
+            .init_asset_loader::<AnimationGraphLoader>();
+    }
+}
@@ -0,0 +1,149 @@
+use crate::inputs::KeyInputState;
+use crate::loading::CharacterAssets;
+use crate::movement::{AnimationGraphAsset,CharacterAnimations,CharacterController,CharacterState,MovementState,Mover,Traversal};
+use crate::settings::SettingsAsset;
+use bevy::prelude::*;
+
+// system state
+#[derive(Default)]
+pub struct AnimationState {
+    // graph state playing, if any
+    pub state: Option<String>,
+    // crossfade out of the pose held when the state changed
+    pub fade_rmn: f32,
+    pub fade_duration: f32,
+    pub pose: Vec<(Entity, Transform)>,
+}
+
+// whether a transition condition holds, a leading ! negates it
+fn animation_condition(
+    condition: &str,
+    movement_state: &MovementState,
+    key_state: &KeyInputState,
+    controller: &CharacterController,
+    traversal: &Traversal,
+) -> bool {
+    if let Some(condition) = condition.strip_prefix('!') {
+        return !animation_condition(condition, movement_state, key_state, controller, traversal);
+    }
+    match condition {
+        "interacting" => movement_state.toggle_switch_rmn > 0.0001,
+        "traversing" => traversal.mode.is_some(),
+        "grounded" => controller.grounded,
+        "moving" => controller.wish_velocity.length_squared() > 0.0001,
+        "running" => key_state.run,
+        "crouched" => controller.crouched(),
+        _ => {
+            println!("unknown animation condition :: {:?}", condition);
+            false
+        }
+    }
+}
+
+// every transform under the animation player, as it is now
+fn animation_pose(
+    entity: Entity,
+    children: &Query<&Children>,
+    transforms: &Query<&Transform>,
+    pose: &mut Vec<(Entity, Transform)>,
+) {
+    if let Ok(transform) = transforms.get(entity) {
+        pose.push((entity, *transform));
+    }
+    if let Ok(entity_children) = children.get(entity) {
+        for child in entity_children.iter() {
+            animation_pose(*child, children, transforms, pose);
+        }
+    }
+}
+
+// follows the first transition out of the playing state whose conditions hold
+pub fn update_animation_graph(
+    animations: Res<CharacterAnimations>,
+    animation_graphs: Res<Assets<AnimationGraphAsset>>,
+    character_handles: Res<CharacterAssets>,
+    character_state: Res<CharacterState>,
+    key_state: Res<KeyInputState>,
+    movement_state: Res<MovementState>,
+    settings: Res<SettingsAsset>,
+    mut animation_state: ResMut<AnimationState>,
+    mover_query: Query<(&CharacterController, &Traversal), With<Mover>>,
+    mut animation_players: Query<(Entity, &Parent, &mut AnimationPlayer)>,
+    children: Query<&Children>,
+    transforms: Query<&Transform>,
+) {
+    // skip gltf in colliders mode
+    if movement_state.noclip || settings.graphics_settings.render_mode.as_str() == "colliders" {
+        return;
+    }
+    let graph = match animation_graphs.get(&character_handles.graham_animation_graph) {
+        Some(graph) => graph,
+        None => return,
+    };
+    let (player_entity, mut player) = match animation_players.iter_mut()
+        .find(|(_, parent, _)| character_state.character_anim_entity == Some(parent.get())) {
+        Some((entity, _, player)) => (entity, player),
+        None => return,
+    };
+
+    let (controller, traversal) = mover_query.single();
+    let current = animation_state.state.clone();
+    let transition = graph.transitions.iter().find(|transition| {
+        (transition.from.is_empty() || current.as_ref().is_some_and(|state| transition.from.contains(state)))
+            && transition.conditions.iter().all(|condition| {
+                animation_condition(condition, &movement_state, &key_state, controller, traversal)
+            })
+    });
+    let (next, fade) = match (transition, &current) {
+        (Some(transition), _) => (transition.to.clone(), transition.fade),
+        (None, Some(state)) => (state.clone(), 0.0),
+        (None, None) => (graph.initial.clone(), 0.0),
+    };
+    if current.as_ref() == Some(&next) {
+        return;
+    }
+
+    // states without a clip were reported when the graph loaded, keep playing the current one
+    let (state, clip) = match graph.states.get(&next)
+        .and_then(|state| animations.0.get(&state.clip).map(|clip| (state, clip))) {
+        Some(resolved) => resolved,
+        None => return,
+    };
+    animation_state.state = Some(next);
+
+    // fade from wherever the bones are, even part way through another fade
+    animation_state.pose.clear();
+    animation_state.fade_rmn = 0.0;
+    if current.is_some() && fade > f32::EPSILON {
+        animation_pose(player_entity, &children, &transforms, &mut animation_state.pose);
+        animation_state.fade_rmn = fade;
+        animation_state.fade_duration = fade;
+    }
+    player.play(clip.clone_weak()).set_speed(state.speed);
+    if state.repeat {
+        player.repeat();
+    }
+}
+
+// blends the pose sampled from the new clip with the pose held from the old one
+pub fn update_animation_blend(
+    time: Res<Time>,
+    mut animation_state: ResMut<AnimationState>,
+    mut transforms: Query<&mut Transform>,
+) {
+    if animation_state.fade_rmn <= 0.0 {
+        return;
+    }
+    animation_state.fade_rmn -= time.delta_seconds();
+    let t = 1.0 - (animation_state.fade_rmn / animation_state.fade_duration).max(0.0);
+    for (entity, from) in animation_state.pose.iter() {
+        if let Ok(mut transform) = transforms.get_mut(*entity) {
+            transform.translation = from.translation.lerp(transform.translation, t);
+            transform.rotation = from.rotation.slerp(transform.rotation, t);
+            transform.scale = from.scale.lerp(transform.scale, t);
+        }
+    }
+    if animation_state.fade_rmn <= 0.0 {
+        animation_state.pose.clear();
+    }
+}
@@ -0,0 +1,253 @@
+use crate::movement::MoverParent;
+use crate::world::PlatformRider;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+// m/s² down, and m/s up when jumping
+const GRAVITY: f32 = 9.81;
+const JUMP_SPEED: f32 = 4.5;
+// seconds after walking off an edge that a jump still counts
+const COYOTE_TIME: f32 = 0.15;
+// steepest walkable ground in degrees, and the tallest step climbed without jumping
+const MAX_SLOPE_ANGLE: f32 = 45.0;
+const STEP_HEIGHT: f32 = 0.3;
+// gap kept between the capsule and whatever it touches
+const SKIN_WIDTH: f32 = 0.02;
+// m/s² towards the wished velocity, on the ground and in the air
+const GROUND_ACCELERATION: f32 = 30.0;
+const AIR_ACCELERATION: f32 = 5.0;
+// half height of the capsule's straight section when crouched, and m/s it changes at
+const CROUCH_HALF_HEIGHT: f32 = 0.1;
+const CROUCH_RATE: f32 = 2.0;
+// times a move slides along what it hits before giving up
+const MAX_SLIDES: usize = 4;
+
+// moves a kinematic capsule, set wish_velocity, jump and crouch each frame
+#[derive(Clone,Component,Copy)]
+pub struct CharacterController {
+    pub radius: f32,
+    pub stand_half_height: f32,
+    pub half_height: f32,
+    pub wish_velocity: Vec3,
+    pub jump: bool,
+    pub crouch: bool,
+    pub velocity: Vec3,
+    pub grounded: bool,
+    pub ground_normal: Vec3,
+    pub coyote_rmn: f32,
+    // left alone while something else moves the body
+    pub suspended: bool,
+}
+
+impl CharacterController {
+    pub fn new(
+        radius: f32,
+        half_height: f32,
+    ) -> Self {
+        Self {
+            radius,
+            stand_half_height: half_height,
+            half_height,
+            wish_velocity: Vec3::ZERO,
+            jump: false,
+            crouch: false,
+            velocity: Vec3::ZERO,
+            grounded: false,
+            ground_normal: Vec3::Y,
+            coyote_rmn: 0.0,
+            suspended: false,
+        }
+    }
+
+    // distance from the capsule center down to its feet
+    pub fn foot_offset(&self) -> f32 {
+        self.half_height + self.radius
+    }
+
+    pub fn crouched(&self) -> bool {
+        self.half_height < self.stand_half_height - f32::EPSILON
+    }
+}
+
+// first thing the capsule hits moving along direction, passing out of anything it starts inside
+pub fn cast_capsule(
+    rapier_context: &RapierContext,
+    collider: &Collider,
+    position: Vec3,
+    direction: Vec3,
+    distance: f32,
+    filter: QueryFilter,
+) -> Option<Toi> {
+    let mut filter = filter;
+    for _ in 0..MAX_SLIDES {
+        match rapier_context.cast_shape(position, Quat::IDENTITY, direction, collider, distance, filter) {
+            Some((entity, toi)) if toi.status == TOIStatus::Penetrating => {
+                filter = filter.exclude_collider(entity);
+            }
+            hit => return hit.map(|(_, toi)| toi),
+        }
+    }
+    None
+}
+
+// moves as far as it can, sliding along what it hits
+// walls too steep to walk are flattened so sliding along them never climbs
+// returns the end position and the normal of the first hit
+fn slide_capsule(
+    rapier_context: &RapierContext,
+    collider: &Collider,
+    position: Vec3,
+    motion: Vec3,
+    filter: QueryFilter,
+    flatten_walls: bool,
+) -> (Vec3, Option<Vec3>) {
+    let walkable = MAX_SLOPE_ANGLE.to_radians().cos();
+    let (mut position, mut motion) = (position, motion);
+    let mut first_normal: Option<Vec3> = None;
+    for _ in 0..MAX_SLIDES {
+        let distance = motion.length();
+        if distance < f32::EPSILON {
+            break;
+        }
+        let direction = motion / distance;
+        match cast_capsule(rapier_context, collider, position, direction, distance + SKIN_WIDTH, filter) {
+            Some(toi) => {
+                let travel = (toi.toi - SKIN_WIDTH).clamp(0.0, distance);
+                position += direction * travel;
+                first_normal.get_or_insert(toi.normal1);
+                let mut normal = toi.normal1;
+                if flatten_walls && normal.y < walkable {
+                    normal = Vec3::new(normal.x, 0.0, normal.z).normalize_or_zero();
+                }
+                motion = direction * (distance - travel);
+                motion -= normal * motion.dot(normal).min(0.0);
+            }
+            None => {
+                position += motion;
+                break;
+            }
+        }
+    }
+    (position, first_normal)
+}
+
+// crouches, jumps, falls and walks every controller, climbing steps and keeping off steep slopes
+pub fn update_character_controllers(
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    mut controllers: Query<(Entity, &mut Transform, &mut CharacterController, &mut Collider, Option<&mut PlatformRider>)>,
+    mut mover_parents: Query<(&Parent, &mut Transform), (With<MoverParent>, Without<CharacterController>)>,
+) {
+    let dt = time.delta_seconds();
+    if dt <= 0.0 {
+        return;
+    }
+    let walkable = MAX_SLOPE_ANGLE.to_radians().cos();
+
+    for (entity, mut transform, mut controller, mut collider, mut rider) in controllers.iter_mut() {
+        if controller.suspended {
+            controller.grounded = false;
+            continue;
+        }
+        let filter = QueryFilter { groups: Some(InteractionGroups::new(0b0001, 0b0001)), ..Default::default() }
+            .exclude_sensors()
+            .exclude_rigid_body(entity);
+
+        // crouch, rising only as far as the headroom allows
+        let target = if controller.crouch { CROUCH_HALF_HEIGHT.min(controller.stand_half_height) } else { controller.stand_half_height };
+        let step = CROUCH_RATE * dt;
+        let mut half_height = controller.half_height + (target - controller.half_height).clamp(-step, step);
+        if half_height > controller.half_height {
+            let growth = half_height - controller.half_height;
+            if let Some(toi) = cast_capsule(&rapier_context, &collider, transform.translation, Vec3::Y, 2.0 * growth + SKIN_WIDTH, filter) {
+                half_height = controller.half_height + (0.5 * (toi.toi - SKIN_WIDTH)).max(0.0);
+            }
+        }
+        if (half_height - controller.half_height).abs() > f32::EPSILON {
+            // feet stay where they are
+            let shift = half_height - controller.half_height;
+            transform.translation.y += shift;
+            controller.half_height = half_height;
+            *collider = Collider::capsule_y(half_height, controller.radius);
+            if let Some(rider) = rider.as_mut() {
+                rider.ground_distance = controller.foot_offset();
+            }
+            for (parent, mut parent_transform) in mover_parents.iter_mut() {
+                if parent.get() == entity {
+                    parent_transform.translation.y -= shift;
+                }
+            }
+        }
+
+        // jumps count for a moment after leaving the ground
+        if controller.grounded {
+            controller.coyote_rmn = COYOTE_TIME;
+        } else {
+            controller.coyote_rmn -= dt;
+        }
+        let mut vertical_speed = controller.velocity.y;
+        if controller.jump && controller.coyote_rmn > 0.0 {
+            vertical_speed = JUMP_SPEED;
+            controller.coyote_rmn = 0.0;
+            controller.grounded = false;
+        }
+        controller.jump = false;
+        if !controller.grounded {
+            vertical_speed -= GRAVITY * dt;
+        }
+
+        // carried along by the platform underfoot
+        let mut start = transform.translation;
+        if let Some(delta) = rider.as_ref().map(|rider| rider.delta).filter(|delta| delta.length_squared() > 0.0) {
+            (start, _) = slide_capsule(&rapier_context, &collider, start, delta, filter, false);
+        }
+
+        let acceleration = if controller.grounded { GROUND_ACCELERATION } else { AIR_ACCELERATION } * dt;
+        let mut horizontal_velocity = controller.velocity * Vec3::new(1.0, 0.0, 1.0);
+        horizontal_velocity += (controller.wish_velocity - horizontal_velocity).clamp_length_max(acceleration);
+
+        // across, stepping up onto whatever blocks the way
+        let horizontal_motion = horizontal_velocity * dt;
+        let (mut position, hit_normal) = slide_capsule(&rapier_context, &collider, start, horizontal_motion, filter, true);
+        if controller.grounded && hit_normal.is_some_and(|normal| normal.y < walkable) {
+            let rise = cast_capsule(&rapier_context, &collider, start, Vec3::Y, STEP_HEIGHT + SKIN_WIDTH, filter)
+                .map_or(STEP_HEIGHT, |toi| (toi.toi - SKIN_WIDTH).max(0.0));
+            let (raised, _) = slide_capsule(&rapier_context, &collider, start + rise * Vec3::Y, horizontal_motion, filter, true);
+            let landing = cast_capsule(&rapier_context, &collider, raised, -Vec3::Y, rise + SKIN_WIDTH, filter)
+                .filter(|toi| toi.normal1.y >= walkable);
+            if let Some(landing) = landing {
+                let stepped = raised - (landing.toi - SKIN_WIDTH).max(0.0) * Vec3::Y;
+                let progress = |end: Vec3| ((end - start) * Vec3::new(1.0, 0.0, 1.0)).length();
+                if progress(stepped) > progress(position) + f32::EPSILON {
+                    position = stepped;
+                }
+            }
+        }
+
+        // up or down, sliding off slopes too steep to stand on
+        let (fallen, hit_normal) = slide_capsule(&rapier_context, &collider, position, vertical_speed * dt * Vec3::Y, filter, false);
+        position = fallen;
+        if let Some(normal) = hit_normal {
+            if (vertical_speed > 0.0 && normal.y < 0.0) || (vertical_speed < 0.0 && normal.y >= walkable) {
+                vertical_speed = 0.0;
+            }
+        }
+
+        // grounded on walkable ground, held to it going down slopes and stairs
+        let probe = if controller.grounded { STEP_HEIGHT } else { 2.0 * SKIN_WIDTH };
+        controller.grounded = false;
+        if vertical_speed <= 0.0 {
+            if let Some(toi) = cast_capsule(&rapier_context, &collider, position, -Vec3::Y, probe + SKIN_WIDTH, filter) {
+                if toi.normal1.y >= walkable {
+                    position.y -= (toi.toi - SKIN_WIDTH).max(0.0);
+                    controller.grounded = true;
+                    controller.ground_normal = toi.normal1;
+                    vertical_speed = 0.0;
+                }
+            }
+        }
+
+        transform.translation = position;
+        controller.velocity = horizontal_velocity + vertical_speed * Vec3::Y;
+    }
+}
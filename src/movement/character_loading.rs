@@ -1,15 +1,18 @@
 use crate::game_state::GameState;
 use crate::loading::{CharacterAssets};
-use crate::movement::{CharacterState,Mover,MoverParent};
+use crate::movement::{AnimationGraphAsset,CharacterController,CharacterState,Mover,MoverParent,Traversal};
 use crate::settings::SettingsAsset;
-use crate::world::WorldState;
+use crate::world::{PlatformRider,WorldState};
+use bevy::gltf::Gltf;
 use bevy::prelude::*;
 use bevy::scene::InstanceId;
 use bevy_rapier3d::prelude::*;
+use std::collections::HashMap;
 
 pub struct CharacterLoadingPlugin;
 
-pub struct CharacterAnimations(pub Vec<Handle<AnimationClip>>);
+// clips by gltf animation name
+pub struct CharacterAnimations(pub HashMap<String, Handle<AnimationClip>>);
 
 #[derive(Default)]
 pub struct CharacterLoadingState {
@@ -43,19 +46,17 @@ fn setup_character_loading(
     let half_height = 0.54;
     commands
         .spawn_bundle(SpatialBundle::from_transform(
-            Transform::from_translation(Vec3::new(0.0,0.5,0.0))))
+            // clear of the ground, the controller will not move out of it
+            Transform::from_translation(Vec3::new(0.0,half_height + radius + 0.05,0.0))))
         .insert(Mover::default())
-        .insert(RigidBody::Dynamic)
-        .insert(Collider::capsule(-half_height * Vec3::Y, half_height * Vec3::Y, radius))
+        .insert(RigidBody::KinematicPositionBased)
+        .insert(CharacterController::new(radius, half_height))
+        .insert(Traversal::default())
+        .insert(PlatformRider { ground_distance: half_height + radius, ..Default::default() })
+        .insert(Collider::capsule_y(half_height, radius))
         .insert(CollisionGroups::new(0b0001, 0b0001))
-
-        .insert(LockedAxes::ROTATION_LOCKED)
-        .insert(Ccd::enabled())
-        .insert(ExternalImpulse {
-            impulse: Vec3::ZERO,
-            torque_impulse: Vec3::ZERO,
-        })
-        .insert(Damping { linear_damping: 10.0, angular_damping: 1.0 })
+        // doors and platforms are kinematic or fixed, and still need to see the player
+        .insert(ActiveCollisionTypes::default() | ActiveCollisionTypes::KINEMATIC_STATIC | ActiveCollisionTypes::KINEMATIC_KINEMATIC)
         .with_children(|parent| {
             if settings.graphics_settings.render_mode.as_str() == "colliders" {
                 // spawn character container
@@ -97,17 +98,29 @@ fn setup_character_loading(
 
 fn setup_character_animations(
     mut commands: Commands,
-    asset_server: Res<AssetServer>,
+    character_handles: Res<CharacterAssets>,
+    gltfs: Res<Assets<Gltf>>,
+    animation_graphs: Res<Assets<AnimationGraphAsset>>,
 ) {
     // Insert a resource with the current scene information
-    commands.insert_resource(CharacterAnimations(vec![
-        asset_server.load("characters/graham_v4.glb#Animation0"),
-        asset_server.load("characters/graham_v4.glb#Animation1"),
-        asset_server.load("characters/graham_v4.glb#Animation2"),
-        asset_server.load("characters/graham_v4.glb#Animation3"),
-        asset_server.load("characters/graham_v4.glb#Animation4"),
-        asset_server.load("characters/graham_v4.glb#Animation5"),
-    ]));
+    let clips: HashMap<String, Handle<AnimationClip>> = match gltfs.get(&character_handles.graham_gltf) {
+        Some(gltf) => gltf.named_animations.clone().into_iter().collect(),
+        None => HashMap::new(),
+    };
+    // report graph states without a clip once, they are never entered
+    if let Some(graph) = animation_graphs.get(&character_handles.graham_animation_graph) {
+        for (name, state) in graph.states.iter() {
+            if !clips.contains_key(&state.clip) {
+                println!("unknown animation clip :: {:?} {:?}", name, state.clip);
+            }
+        }
+        for to in graph.transitions.iter().map(|transition| &transition.to).chain([&graph.initial]) {
+            if !graph.states.contains_key(to) {
+                println!("unknown animation state :: {:?}", to);
+            }
+        }
+    }
+    commands.insert_resource(CharacterAnimations(clips));
 }
 
 fn update_character_loading(
@@ -1,28 +1,39 @@
 use crate::game_state::GameState;
 use crate::inputs::{KeyInputState,MouseCamera,MouseLookState};
-use crate::movement::{CharacterState,CharacterAnimations};
+use crate::loading::CharacterAssets;
+use crate::movement::{AnimationGraphAsset,AnimationState,CharacterController,CharacterState,CharacterAnimations,
+    update_animation_blend,update_animation_graph,update_character_controllers,update_traversal};
 use crate::settings::SettingsAsset;
-use crate::world::{SoundsEvent,SoundsEventAction,WorldState};
+use crate::world::{SoundsEvent,SoundsEventAction,Surface,WorldState};
+use bevy::animation::animation_player;
 use bevy::prelude::*;
+use bevy::transform::TransformSystem;
+use bevy_kira_audio::prelude::*;
 use bevy_rapier3d::prelude::*;
 
-const MOVE_SPEED: f32 = 20.0;
+// m/s
+const MOVE_SPEED: f32 = 1.5;
 const RUN_SPEED: f32 = MOVE_SPEED * 2.0;
+const CROUCH_MOVE_SPEED: f32 = MOVE_SPEED * 0.5;
 const CAMERA_FLY_MOVE_SPEED: f32 = 10.0;
+// meters the camera target may trail the player before snapping to it
+const CAMERA_SNAP_DISTANCE: f32 = 5.0;
 
-// const RESET_ANIMATION_IDX: usize = 1;
-const IDLE_ANIMATION_IDX: usize = 0;
-const TOGGLE_SWITCH_ANIMATION_IDX: usize = 4;
-const FORWARD_ANIMATION_IDX: usize = 5;
-const FORWARD_RUN_ANIMATION_IDX: usize = 2;
-// const TAKE_ITEM_ANIMATION_IDX: usize = 3;
+// clips of states with footsteps hold a stride of two steps
+const FOOTSTEPS_PER_CYCLE: f32 = 2.0;
+// from the capsule center to just under its feet
+const FOOTSTEP_RAY_LENGTH: f32 = 1.6;
 
 // system state
 #[derive(Default)]
 pub struct MovementState {
     pub noclip: bool,
-    pub playing_animation: Option<usize>,
     pub toggle_switch_rmn: f32,
+    // animation state and step of the last footstep
+    pub last_footstep: Option<(String, u32)>,
+    // smoothed third person camera target, and its distance behind it
+    pub camera_pivot: Option<Vec3>,
+    pub camera_distance: f32,
 }
 
 // marks the rigid body of the player character
@@ -51,11 +62,23 @@ pub struct MovementStatePlugin;
 impl Plugin for MovementStatePlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<MovementState>();
+        app.init_resource::<AnimationState>();
         app.add_system_set(
             SystemSet::on_update(GameState::Running)
             .with_system(update_movement)
-            .with_system(update_camera)
+            .with_system(update_traversal.after(update_movement))
+            .with_system(update_character_controllers.after(update_traversal))
+            .with_system(update_camera.after(update_character_controllers))
             .with_system(update_character_state)
+            .with_system(update_animation_graph.after(update_character_state).after(update_character_controllers))
+            .with_system(update_footsteps.after(update_animation_graph))
+        );
+        // crossfades blend what the animation player sampled, before it moves the meshes
+        app.add_system_to_stage(
+            CoreStage::PostUpdate,
+            update_animation_blend
+                .after(animation_player)
+                .before(TransformSystem::TransformPropagate),
         );
     }
 }
@@ -65,10 +88,10 @@ fn update_movement(
     key_state: Res<KeyInputState>,
     mouse_look: Res<MouseLookState>,
     mut movement_state: ResMut<MovementState>,
-    mut mover_query: Query<(&mut ExternalImpulse, &mut Mover)>,
+    mut mover_query: Query<(&mut CharacterController, &mut Mover)>,
     world_state: Res<WorldState>,
 ) {
-    let (mut mover_impulse, mut mover) = mover_query.single_mut();
+    let (mut controller, mut mover) = mover_query.single_mut();
 
     // update state
     if key_state.toggle_fly {
@@ -78,16 +101,18 @@ fn update_movement(
         mover.third_person = !mover.third_person;
     }
 
-    if movement_state.noclip {
-        return;
-    }
-    if world_state.active_train.is_some() {
+    // stand still while flying the camera or driving
+    if movement_state.noclip || world_state.active_train.is_some() {
+        controller.wish_velocity = Vec3::ZERO;
+        controller.jump = false;
+        controller.crouch = false;
         return;
     }
 
     let mouse_forward = (mouse_look.forward * Vec3::new(1.0, 0.0, 1.0)).normalize();
     let mouse_right = (mouse_look.right * Vec3::new(1.0, 0.0, 1.0)).normalize();
-    let wish_move = (if key_state.run { RUN_SPEED } else { MOVE_SPEED }) * time.delta_seconds() * (
+    let speed = if controller.crouched() { CROUCH_MOVE_SPEED } else if key_state.run { RUN_SPEED } else { MOVE_SPEED };
+    let wish_velocity = speed * (
         if key_state.forward { mouse_forward } else { Vec3::ZERO } +
         if key_state.backward { -mouse_forward } else { Vec3::ZERO } +
         if key_state.right { mouse_right } else { Vec3::ZERO } +
@@ -95,21 +120,25 @@ fn update_movement(
     );
 
     // store last move
-    mover.last_wish_move = wish_move;
+    mover.last_wish_move = wish_velocity * time.delta_seconds();
     // apply move
-    mover_impulse.impulse = wish_move;
+    controller.wish_velocity = wish_velocity;
+    controller.jump = key_state.jump;
+    controller.crouch = key_state.crouch;
 }
 
 // update camera position from movement
 fn update_camera(
     time: Res<Time>,
     key_state: Res<KeyInputState>,
-    movement_state: Res<MovementState>,
+    rapier_context: Res<RapierContext>,
+    settings: Res<SettingsAsset>,
+    mut movement_state: ResMut<MovementState>,
     mouse_look: Res<MouseLookState>,
-    mover_query: Query<(&Transform, &Mover), Without<MouseCamera>>,
+    mover_query: Query<(Entity, &Transform, &Mover), Without<MouseCamera>>,
     mut query: Query<&mut Transform, With<MouseCamera>>,
 ) {
-    let (mover_transform, mover) = mover_query.single();
+    let (mover_entity, mover_transform, mover) = mover_query.single();
     for mut camera in query.iter_mut() {
         if movement_state.noclip {
             let camera_move = CAMERA_FLY_MOVE_SPEED * time.delta_seconds() * (
@@ -128,10 +157,42 @@ fn update_camera(
             camera.look_at(next_position + mouse_look.forward, Vec3::Y);
         } else {
             if mover.third_person {
-                let mover_position = mover_transform.translation.clone() + 0.8 * Vec3::Y;
-                camera.translation = mover_position - (mouse_look.forward * (1.0 + mouse_look.zoom));
-                camera.look_at(mover_position, Vec3::Y);
+                let camera_settings = &settings.camera_settings;
+                let dt = time.delta_seconds();
+                let mover_position = mover_transform.translation + 0.8 * Vec3::Y;
+
+                // trail the player, catching up at once after a teleport
+                let pivot = match movement_state.camera_pivot {
+                    Some(pivot) if pivot.distance(mover_position) < CAMERA_SNAP_DISTANCE => {
+                        pivot.lerp(mover_position, smoothing_factor(camera_settings.position_smoothing, dt))
+                    }
+                    _ => mover_position,
+                };
+                movement_state.camera_pivot = Some(pivot);
+                let rotation = camera.rotation.slerp(
+                    Transform::default().looking_at(mouse_look.forward, Vec3::Y).rotation,
+                    smoothing_factor(camera_settings.rotation_smoothing, dt));
+                let back = rotation * Vec3::Z;
+
+                // pull in at once when blocked, ease back out when clear
+                let ray_filter = QueryFilter { groups: Some(InteractionGroups::new(0b0001, 0b0001)), ..Default::default() }
+                    .exclude_sensors()
+                    .exclude_rigid_body(mover_entity);
+                let zoom_distance = 1.0 + mouse_look.zoom;
+                let clear_distance = rapier_context.cast_shape(
+                    pivot, Quat::IDENTITY, back, &Collider::ball(camera_settings.collision_radius), zoom_distance, ray_filter
+                ).map_or(zoom_distance, |(_, toi)| toi.toi);
+                movement_state.camera_distance = if clear_distance < movement_state.camera_distance {
+                    clear_distance
+                } else {
+                    movement_state.camera_distance + (clear_distance - movement_state.camera_distance)
+                        * smoothing_factor(camera_settings.position_smoothing, dt)
+                };
+
+                camera.translation = pivot + back * movement_state.camera_distance;
+                camera.rotation = rotation;
             } else {
+                movement_state.camera_pivot = None;
                 let mouse_forward = (mouse_look.forward * Vec3::new(1.0, 0.0, 1.0)).normalize();
                 let mover_position = mover_transform.translation.clone() + 0.8 * Vec3::Y + 0.15 * mouse_forward;
                 camera.translation = mover_position;
@@ -141,17 +202,24 @@ fn update_camera(
     }
 }
 
+// share of the way to the target covered this frame, at rate per second
+fn smoothing_factor(
+    rate: f32,
+    dt: f32,
+) -> f32 {
+    if rate > 0.0 {
+        1.0 - (-rate * dt).exp()
+    } else {
+        1.0
+    }
+}
+
 fn update_character_state(
-    animations: Res<CharacterAnimations>,
     mut mover_parent_query: Query<&mut Transform, With<MoverParent>>,
     mover_query: Query<&Mover>,
     time: Res<Time>,
-    character_state: Res<CharacterState>,
     mut movement_state: ResMut<MovementState>,
-    mut animation_players: Query<(&Parent, &mut AnimationPlayer)>,
-    mut sounds_events: EventWriter<SoundsEvent>,
     settings: Res<SettingsAsset>,
-    key_state: Res<KeyInputState>,
 ) {
     if movement_state.noclip {
         return;
@@ -179,48 +247,76 @@ fn update_character_state(
         return;
     }
 
-    // update animation state
-    for (parent, mut player) in animation_players.iter_mut() {
-        if character_state.character_anim_entity.is_some() && character_state.character_anim_entity.unwrap() == parent.get() {
-            if movement_state.toggle_switch_rmn > 0.0001 {
-                movement_state.toggle_switch_rmn -= time.delta_seconds();
-                if !(movement_state.playing_animation.is_some() && movement_state.playing_animation.unwrap() == TOGGLE_SWITCH_ANIMATION_IDX) {
-                    movement_state.playing_animation = Some(TOGGLE_SWITCH_ANIMATION_IDX);
-                    player.play(animations.0[TOGGLE_SWITCH_ANIMATION_IDX].clone_weak()).repeat();
-                }
-            } else if move_forward.length_squared() > 0.0001 {
-                if key_state.run {
-                    if !(movement_state.playing_animation.is_some() && movement_state.playing_animation.unwrap() == FORWARD_RUN_ANIMATION_IDX) {
-                        movement_state.playing_animation = Some(FORWARD_RUN_ANIMATION_IDX);
-                        player.play(animations.0[FORWARD_RUN_ANIMATION_IDX].clone_weak()).repeat();
-                        // play footsteps
-                        sounds_events.send(SoundsEvent {
-                            action: SoundsEventAction::Resume,
-                            name: "footsteps".into(),
-                        });
-                    }
-                } else {
-                    if !(movement_state.playing_animation.is_some() && movement_state.playing_animation.unwrap() == FORWARD_ANIMATION_IDX) {
-                        movement_state.playing_animation = Some(FORWARD_ANIMATION_IDX);
-                        player.play(animations.0[FORWARD_ANIMATION_IDX].clone_weak()).repeat();
-                        // play footsteps
-                        sounds_events.send(SoundsEvent {
-                            action: SoundsEventAction::Resume,
-                            name: "footsteps".into(),
-                        });
-                    }
-                }
-            } else {
-                if !(movement_state.playing_animation.is_some() && movement_state.playing_animation.unwrap() == IDLE_ANIMATION_IDX) {
-                    movement_state.playing_animation = Some(IDLE_ANIMATION_IDX);
-                    player.play(animations.0[IDLE_ANIMATION_IDX].clone_weak()).repeat();
-                    // pause footsteps
-                    sounds_events.send(SoundsEvent {
-                        action: SoundsEventAction::Pause,
-                        name: "footsteps".into(),
-                    });
-                }
+    if movement_state.toggle_switch_rmn > 0.0001 {
+        movement_state.toggle_switch_rmn -= time.delta_seconds();
+    }
+}
+
+// steps on the beat of the walk and run animations, with the sound of the ground underfoot
+fn update_footsteps(
+    rapier_context: Res<RapierContext>,
+    animation_clips: Res<Assets<AnimationClip>>,
+    animations: Res<CharacterAnimations>,
+    animation_graphs: Res<Assets<AnimationGraphAsset>>,
+    animation_state: Res<AnimationState>,
+    character_handles: Res<CharacterAssets>,
+    character_state: Res<CharacterState>,
+    world_state: Res<WorldState>,
+    audio: Res<DynamicAudioChannels>,
+    mut movement_state: ResMut<MovementState>,
+    mut sounds_events: EventWriter<SoundsEvent>,
+    mover_query: Query<(Entity, &GlobalTransform), With<Mover>>,
+    animation_players: Query<(&Parent, &AnimationPlayer)>,
+    surfaces: Query<&Surface>,
+) {
+    let graph = animation_graphs.get(&character_handles.graham_animation_graph);
+    let (animation, state) = match (&animation_state.state, graph) {
+        (Some(animation), Some(graph)) if !movement_state.noclip => match graph.states.get(animation) {
+            Some(state) if state.footsteps => (animation, state),
+            _ => {
+                movement_state.last_footstep = None;
+                return;
             }
+        },
+        _ => {
+            movement_state.last_footstep = None;
+            return;
         }
+    };
+    let player = match animation_players.iter()
+        .find(|(parent, _)| character_state.character_anim_entity == Some(parent.get())) {
+        Some((_, player)) => player,
+        None => return,
+    };
+    let duration = match animations.0.get(&state.clip).and_then(|clip| animation_clips.get(clip)) {
+        Some(clip) if clip.duration() > f32::EPSILON => clip.duration(),
+        _ => return,
+    };
+    let step = (player.elapsed() / duration * FOOTSTEPS_PER_CYCLE) as u32;
+    if movement_state.last_footstep.as_ref() == Some(&(animation.clone(), step)) {
+        return;
     }
+    movement_state.last_footstep = Some((animation.clone(), step));
+
+    // no step in the air, untagged ground sounds as the world default
+    let (mover_entity, mover_transform) = mover_query.single();
+    let ray_filter = QueryFilter { groups: Some(InteractionGroups::new(0b0001, 0b0001)), ..Default::default() }
+        .exclude_rigid_body(mover_entity);
+    let ground = match rapier_context.cast_ray(mover_transform.translation(), -Vec3::Y, FOOTSTEP_RAY_LENGTH, true, ray_filter) {
+        Some((ground, _)) => ground,
+        None => return,
+    };
+    let footsteps = &world_state.footsteps;
+    let surface = surfaces.get(ground).map_or(&footsteps.surface, |surface| &surface.surface);
+    // surfaces still without a sound of their own step as the world surface
+    let sound = match footsteps.sounds.get(surface).or_else(|| footsteps.sounds.get(&footsteps.surface)) {
+        Some(sound) => sound,
+        None => return,
+    };
+    // a step cuts off the one before
+    audio.channel("footsteps").stop();
+    sounds_events.send(SoundsEvent {
+        action: SoundsEventAction::PlayOnce(sound.clone(), None),
+        name: "footsteps".into(),
+    });
 }
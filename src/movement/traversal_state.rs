@@ -0,0 +1,269 @@
+use crate::inputs::{KeyInputState,MouseLookState};
+use crate::movement::{CharacterController,MovementState,Mover,cast_capsule};
+use crate::world::{WorldState,WorldTraversal};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+// m/s up and down ladders
+const CLIMB_SPEED: f32 = 1.2;
+// feet below the top of a ladder when stepping on or off it there
+const LADDER_REACH: f32 = 1.0;
+// seconds to be carried onto a ladder, over a ledge or over an obstacle
+const MOUNT_DURATION: f32 = 0.3;
+const LEDGE_DURATION: f32 = 0.8;
+const VAULT_DURATION: f32 = 0.6;
+// room left between the player and edges they climb or vault over
+const TRAVERSAL_CLEARANCE: f32 = 0.3;
+// margin the capsule is slimmed by when checking a carry is clear, so the floor it stands on doesn't count
+const TRAVERSAL_SKIN: f32 = 0.05;
+// cosine of the widest angle between the look direction and a volume to use it
+const TRAVERSAL_FACING: f32 = 0.5;
+// m/s the player is pushed off a ladder when jumping from it
+const LADDER_JUMP_SPEED: f32 = 2.0;
+
+pub enum TraversalMode {
+    // holding on to a ladder
+    Climb(WorldTraversal),
+    // carried along a curve bending towards via, then onto a ladder if set
+    Carry {
+        from: Vec3,
+        via: Vec3,
+        to: Vec3,
+        t: f32,
+        duration: f32,
+        then: Option<WorldTraversal>,
+    },
+}
+
+// ladder, ledge or vault the player is on, while set the character controller stands aside
+// a component so it starts over with every spawn of the player
+#[derive(Component, Default)]
+pub struct Traversal {
+    pub mode: Option<TraversalMode>,
+}
+
+// flat direction into the wall or obstacle
+fn traversal_facing(
+    data: &WorldTraversal,
+) -> Vec3 {
+    let facing = data.rotation * -Vec3::Z;
+    Vec3::new(facing.x, 0.0, facing.z).normalize_or_zero()
+}
+
+fn traversal_contains(
+    data: &WorldTraversal,
+    position: Vec3,
+) -> bool {
+    let local = data.rotation.inverse() * (position - data.translation);
+    (local.abs() - data.scale).max_element() <= 0.0
+}
+
+fn traversal_top(
+    data: &WorldTraversal,
+) -> f32 {
+    data.translation.y + data.scale.y
+}
+
+// where the player lands past the top of a ladder or ledge
+fn traversal_landing(
+    data: &WorldTraversal,
+    controller: &CharacterController,
+) -> Vec3 {
+    Vec3::new(data.translation.x, traversal_top(data) + controller.foot_offset(), data.translation.z)
+        + traversal_facing(data) * (data.scale.z + controller.radius + TRAVERSAL_CLEARANCE)
+}
+
+fn carry_point(
+    from: Vec3,
+    via: Vec3,
+    to: Vec3,
+    t: f32,
+) -> Vec3 {
+    from.lerp(via, t).lerp(via.lerp(to, t), t)
+}
+
+// whether the body fits up to the top of the curve, across it and down onto its end
+fn carry_clear(
+    rapier_context: &RapierContext,
+    controller: &CharacterController,
+    filter: QueryFilter,
+    from: Vec3,
+    via: Vec3,
+    to: Vec3,
+) -> bool {
+    let bend = from.y - 2.0 * via.y + to.y;
+    let t = if bend.abs() > f32::EPSILON { ((from.y - via.y) / bend).clamp(0.0, 1.0) } else { 0.0 };
+    let peak = from.y.max(to.y).max(carry_point(from, via, to, t).y);
+    let collider = Collider::capsule_y((controller.half_height - TRAVERSAL_SKIN).max(0.0), controller.radius - TRAVERSAL_SKIN);
+    let path = [from, Vec3::new(from.x, peak, from.z), Vec3::new(to.x, peak, to.z), to];
+    path.windows(2).all(|leg| {
+        let distance = (leg[1] - leg[0]).length();
+        distance < f32::EPSILON
+            || cast_capsule(rapier_context, &collider, leg[0], (leg[1] - leg[0]) / distance, distance, filter).is_none()
+    })
+}
+
+// a volume the player is using the way it asks for, if any
+fn start_traversal(
+    traversals: &[WorldTraversal],
+    key_state: &KeyInputState,
+    look_forward: Vec3,
+    position: Vec3,
+    controller: &CharacterController,
+) -> Option<TraversalMode> {
+    let feet = position.y - controller.foot_offset();
+    for data in traversals {
+        let facing = traversal_facing(data);
+        let looking = look_forward.dot(facing) > TRAVERSAL_FACING;
+        let toward = looking && key_state.forward;
+        let top = traversal_top(data);
+        match data.kind.as_str() {
+            "ladder" => {
+                // on at the bottom, walking into it
+                let ladder = Vec3::new(data.translation.x, position.y, data.translation.z);
+                if toward && traversal_contains(data, position) && feet < top - LADDER_REACH {
+                    return Some(TraversalMode::Carry {
+                        from: position,
+                        via: ladder,
+                        to: ladder,
+                        t: 0.0,
+                        duration: MOUNT_DURATION,
+                        then: Some(data.clone()),
+                    });
+                }
+                // on at the top, backing over the edge so climbing down goes on the same key
+                let landing = traversal_landing(data, controller);
+                let near = ((position - landing) * Vec3::new(1.0, 0.0, 1.0)).length() < data.scale.x.max(controller.radius);
+                if looking && key_state.backward && near && (feet - top).abs() < TRAVERSAL_CLEARANCE {
+                    let to = Vec3::new(data.translation.x, top - LADDER_REACH + controller.foot_offset(), data.translation.z);
+                    return Some(TraversalMode::Carry {
+                        from: position,
+                        via: Vec3::new(to.x, position.y, to.z),
+                        to,
+                        t: 0.0,
+                        duration: LEDGE_DURATION,
+                        then: Some(data.clone()),
+                    });
+                }
+            }
+            // caught in the air and pulled up
+            "ledge" if toward && !controller.grounded && traversal_contains(data, position) => {
+                let to = traversal_landing(data, controller);
+                return Some(TraversalMode::Carry {
+                    from: position,
+                    via: Vec3::new(position.x, to.y + TRAVERSAL_CLEARANCE, position.z),
+                    to,
+                    t: 0.0,
+                    duration: LEDGE_DURATION,
+                    then: None,
+                });
+            }
+            // jumped over, landing as low as it started
+            "vault" if toward && key_state.jump && controller.grounded && traversal_contains(data, position) => {
+                let across = (data.translation - position).dot(facing);
+                let apex = top + controller.foot_offset() + TRAVERSAL_CLEARANCE;
+                let mut via = position + facing * across;
+                // the curve peaks halfway to its control point
+                via.y = 2.0 * apex - position.y;
+                return Some(TraversalMode::Carry {
+                    from: position,
+                    via,
+                    to: position + facing * (across + data.scale.z + controller.radius + TRAVERSAL_CLEARANCE),
+                    t: 0.0,
+                    duration: VAULT_DURATION,
+                    then: None,
+                });
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// mounts, climbs and dismounts ladders and carries the player over ledges and obstacles
+pub fn update_traversal(
+    time: Res<Time>,
+    key_state: Res<KeyInputState>,
+    mouse_look: Res<MouseLookState>,
+    movement_state: Res<MovementState>,
+    world_state: Res<WorldState>,
+    rapier_context: Res<RapierContext>,
+    mut mover_query: Query<(Entity, &mut Transform, &mut CharacterController, &Collider, &mut Mover, &mut Traversal)>,
+) {
+    let (entity, mut transform, mut controller, collider, mut mover, mut traversal) = mover_query.single_mut();
+    if movement_state.noclip || world_state.active_train.is_some() {
+        return;
+    }
+    let filter = QueryFilter { groups: Some(InteractionGroups::new(0b0001, 0b0001)), ..Default::default() }
+        .exclude_sensors()
+        .exclude_rigid_body(entity);
+
+    let dt = time.delta_seconds();
+    let look_forward = (mouse_look.forward * Vec3::new(1.0, 0.0, 1.0)).normalize_or_zero();
+    traversal.mode = match traversal.mode.take() {
+        // refused when anything is in the way of the carry
+        None => start_traversal(&world_state.traversals, &key_state, look_forward, transform.translation, &controller)
+            .filter(|mode| match mode {
+                TraversalMode::Carry { from, via, to, .. } => carry_clear(&rapier_context, &controller, filter, *from, *via, *to),
+                TraversalMode::Climb(_) => true,
+            }),
+        Some(TraversalMode::Climb(ladder)) => {
+            let facing = traversal_facing(&ladder);
+            // face the ladder whichever way the keys go
+            mover.last_wish_move = 0.02 * facing;
+            let climb = if key_state.forward { 1.0 } else { 0.0 } - if key_state.backward { 1.0 } else { 0.0 };
+            // stopped by the ground below or anything overhead
+            let mut blocked = false;
+            if climb != 0.0 {
+                let direction = climb * Vec3::Y;
+                let mut distance = CLIMB_SPEED * dt;
+                if let Some(toi) = cast_capsule(&rapier_context, collider, transform.translation, direction, distance + TRAVERSAL_SKIN, filter) {
+                    distance = (toi.toi - TRAVERSAL_SKIN).clamp(0.0, distance);
+                    blocked = true;
+                }
+                transform.translation += direction * distance;
+            }
+            let feet = transform.translation.y - controller.foot_offset();
+            let reach = traversal_top(&ladder) - LADDER_REACH;
+            let to = traversal_landing(&ladder, &controller);
+            let via = Vec3::new(transform.translation.x, to.y + TRAVERSAL_CLEARANCE, transform.translation.z);
+            if key_state.jump {
+                controller.velocity = -LADDER_JUMP_SPEED * facing;
+                None
+            } else if climb > 0.0 && feet >= reach && carry_clear(&rapier_context, &controller, filter, transform.translation, via, to) {
+                Some(TraversalMode::Carry {
+                    from: transform.translation,
+                    via,
+                    to,
+                    t: 0.0,
+                    duration: LEDGE_DURATION,
+                    then: None,
+                })
+            } else if climb > 0.0 && feet >= reach {
+                // no room to step off at the top, hold on there
+                transform.translation.y = reach + controller.foot_offset();
+                Some(TraversalMode::Climb(ladder))
+            } else if climb < 0.0 && (blocked || feet <= ladder.translation.y - ladder.scale.y) {
+                controller.velocity = Vec3::ZERO;
+                None
+            } else {
+                Some(TraversalMode::Climb(ladder))
+            }
+        }
+        Some(TraversalMode::Carry { from, via, to, t, duration, then }) => {
+            let t = (t + dt / duration.max(f32::EPSILON)).min(1.0);
+            transform.translation = carry_point(from, via, to, t);
+            let heading = (to - from) * Vec3::new(1.0, 0.0, 1.0);
+            if heading.length_squared() > f32::EPSILON {
+                mover.last_wish_move = 0.02 * heading.normalize();
+            }
+            if t >= 1.0 {
+                controller.velocity = Vec3::ZERO;
+                then.map(TraversalMode::Climb)
+            } else {
+                Some(TraversalMode::Carry { from, via, to, t, duration, then })
+            }
+        }
+    };
+    controller.suspended = traversal.mode.is_some();
+}
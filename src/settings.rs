@@ -13,6 +13,10 @@ use crate::loading::SettingsAssets;
 #[uuid = "39cadc56-aa9c-4543-8640-a008b74b4844"]
 pub struct SettingsAsset {
     pub graphics_settings: GraphicsSettings,
+    #[serde(default)]
+    pub audio_settings: AudioSettings,
+    #[serde(default)]
+    pub camera_settings: CameraSettings,
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -20,6 +24,55 @@ pub struct GraphicsSettings {
     pub render_mode: String,
 }
 
+// mixer bus volumes, 0.0 to 1.0
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+    pub ambience: f32,
+    pub ui: f32,
+    pub mute_on_focus_loss: bool,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            music: 1.0,
+            sfx: 1.0,
+            ambience: 1.0,
+            ui: 1.0,
+            mute_on_focus_loss: true,
+        }
+    }
+}
+
+// third person camera, zoom in meters past the closest distance of 1.0
+// smoothing rates are per second, 0.0 turns smoothing off
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CameraSettings {
+    pub min_zoom: f32,
+    pub max_zoom: f32,
+    pub collision_radius: f32,
+    pub position_smoothing: f32,
+    pub rotation_smoothing: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        Self {
+            min_zoom: 0.0,
+            max_zoom: 6.0,
+            collision_radius: 0.2,
+            position_smoothing: 12.0,
+            rotation_smoothing: 16.0,
+        }
+    }
+}
+
 #[derive(Default)]
 pub struct SettingsLoader;
 
@@ -60,4 +113,6 @@ fn setup_settings(
 ) {
     let settings_asset = settings_assets.get(&settings_handles.default_settings).unwrap();
     settings.graphics_settings = settings_asset.graphics_settings.clone();
+    settings.audio_settings = settings_asset.audio_settings.clone();
+    settings.camera_settings = settings_asset.camera_settings.clone();
 }
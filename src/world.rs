@@ -1,13 +1,29 @@
 mod animatable_state;
 pub use animatable_state::*;
+mod colliders_state;
+pub use colliders_state::*;
 mod door_state;
 pub use door_state::*;
+mod echoes_state;
+pub use echoes_state::*;
+mod environment_state;
+pub use environment_state::*;
 mod interactable_state;
 pub use interactable_state::*;
 mod inventory_state;
 pub use inventory_state::*;
 mod lights_state;
 pub use lights_state::*;
+mod mixer_state;
+pub use mixer_state::*;
+mod movers_state;
+pub use movers_state::*;
+mod music_state;
+pub use music_state::*;
+mod platforms_state;
+pub use platforms_state::*;
+mod rails_state;
+pub use rails_state::*;
 mod sounds_state;
 pub use sounds_state::*;
 mod trains_state;
@@ -0,0 +1,175 @@
+use crate::world::WorldState;
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices,VertexAttributeValues};
+use bevy::scene::InstanceId;
+use bevy_rapier3d::prelude::*;
+
+// meshes an auto collider is built from
+#[derive(Clone,Debug)]
+pub enum AutoColliderSource {
+    // spawned scene, once its entities are in place
+    Instance(InstanceId),
+    // scene asset not spawned when only colliders are rendered, and its transform in the host
+    Scene(Handle<Scene>, Transform),
+}
+
+// builds a collider from the meshes of a prop scene, once it is ready
+#[derive(Clone,Component,Debug)]
+pub struct AutoCollider {
+    pub source: AutoColliderSource,
+    // aabb, convex_hull or convex_decomposition
+    pub shape: String,
+    pub groups: CollisionGroups,
+    // door taking the collider for blocking checks
+    pub door: Option<String>,
+}
+
+// ground kind of a world collider, picks the footstep sound
+#[derive(Clone,Component,Debug)]
+pub struct Surface {
+    pub surface: String,
+}
+
+pub struct CollidersStatePlugin;
+
+impl Plugin for CollidersStatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        // scenes finish spawning while loading or running
+        .add_system(update_auto_colliders);
+    }
+}
+
+fn update_auto_colliders(
+    mut commands: Commands,
+    scene_spawner: Res<SceneSpawner>,
+    scenes: Res<Assets<Scene>>,
+    meshes: Res<Assets<Mesh>>,
+    mut world_state: ResMut<WorldState>,
+    hosts: Query<(Entity, &AutoCollider)>,
+    nodes: Query<(&Transform, Option<&Parent>)>,
+    mesh_handles: Query<&Handle<Mesh>>,
+) {
+    for (host_entity, auto_collider) in hosts.iter() {
+        // wait for the meshes, and the scene hierarchy when spawned
+        let meshes_to_host = match &auto_collider.source {
+            AutoColliderSource::Instance(instance) => instance_meshes(*instance, host_entity, &scene_spawner, &nodes, &mesh_handles),
+            AutoColliderSource::Scene(scene, transform) => scene_meshes(scene, transform, &scenes),
+        };
+        let meshes_to_host = match meshes_to_host {
+            Some(meshes_to_host) => meshes_to_host,
+            None => continue,
+        };
+        if meshes_to_host.iter().any(|(mesh_handle, _)| meshes.get(mesh_handle).is_none()) {
+            continue;
+        }
+
+        // gather mesh triangles in the host frame
+        let mut points: Vec<Vec3> = Vec::new();
+        let mut triangles: Vec<[u32; 3]> = Vec::new();
+        for (mesh_handle, to_host) in meshes_to_host.iter() {
+            let mesh = meshes.get(mesh_handle).unwrap();
+            let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+                Some(VertexAttributeValues::Float32x3(positions)) => positions,
+                _ => continue,
+            };
+            let offset = points.len() as u32;
+            points.extend(positions.iter().map(|position| to_host.transform_point3(Vec3::from(*position))));
+            let indices = match mesh.indices() {
+                Some(Indices::U16(indices)) => indices.iter().map(|idx| *idx as u32).collect::<Vec<u32>>(),
+                Some(Indices::U32(indices)) => indices.clone(),
+                None => (0..positions.len() as u32).collect::<Vec<u32>>(),
+            };
+            triangles.extend(indices.chunks_exact(3).map(|tri| [tri[0] + offset, tri[1] + offset, tri[2] + offset]));
+        }
+        commands.entity(host_entity).remove::<AutoCollider>();
+
+        let collider = match auto_collider.shape.as_str() {
+            "aabb" if !points.is_empty() => {
+                let (min, max) = points.iter().fold((Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+                    |(min, max), point| (min.min(*point), max.max(*point)));
+                Some((Collider::cuboid(0.5 * (max.x - min.x), 0.5 * (max.y - min.y), 0.5 * (max.z - min.z)),
+                    Transform::from_translation(0.5 * (min + max))))
+            }
+            "convex_hull" => Collider::convex_hull(&points)
+                .map(|collider| (collider, Transform::default())),
+            "convex_decomposition" if !triangles.is_empty() => {
+                Some((Collider::convex_decomposition(&points, &triangles), Transform::default()))
+            }
+            _ => None,
+        };
+
+        if let Some((collider, collider_transform)) = collider {
+            let collider_entity = commands
+                .spawn_bundle(SpatialBundle::from_transform(collider_transform))
+                .insert(collider)
+                .insert(auto_collider.groups)
+                .id();
+            commands.entity(host_entity).add_child(collider_entity);
+            if let Some(door_state) = auto_collider.door.as_ref().and_then(|door| world_state.doors.get_mut(door)) {
+                door_state.collider_entity = Some(collider_entity);
+            }
+        } else {
+            println!("unknown auto collider :: {:?}", auto_collider);
+        }
+    }
+}
+
+// matrix from an entity frame to one of its ancestors, None until parented
+fn transform_to_ancestor(
+    entity: Entity,
+    ancestor: Entity,
+    nodes: &Query<(&Transform, Option<&Parent>)>,
+) -> Option<Mat4> {
+    let mut matrix = Mat4::IDENTITY;
+    let mut current = entity;
+    while current != ancestor {
+        let (transform, parent) = nodes.get(current).ok()?;
+        matrix = transform.compute_matrix() * matrix;
+        current = parent?.get();
+    }
+    Some(matrix)
+}
+
+// meshes of a spawned scene and their matrices to the host, None until parented
+fn instance_meshes(
+    instance: InstanceId,
+    host_entity: Entity,
+    scene_spawner: &SceneSpawner,
+    nodes: &Query<(&Transform, Option<&Parent>)>,
+    mesh_handles: &Query<&Handle<Mesh>>,
+) -> Option<Vec<(Handle<Mesh>, Mat4)>> {
+    let mut meshes_to_host = Vec::new();
+    for entity in scene_spawner.iter_instance_entities(instance)? {
+        if let Ok(mesh_handle) = mesh_handles.get(entity) {
+            meshes_to_host.push((mesh_handle.clone_weak(), transform_to_ancestor(entity, host_entity, nodes)?));
+        }
+    }
+    Some(meshes_to_host)
+}
+
+// meshes of a scene asset that was never spawned, placed in the host frame by transform
+fn scene_meshes(
+    scene: &Handle<Scene>,
+    transform: &Transform,
+    scenes: &Assets<Scene>,
+) -> Option<Vec<(Handle<Mesh>, Mat4)>> {
+    let world = &scenes.get(scene)?.world;
+    let mut meshes_to_host = Vec::new();
+    for archetype in world.archetypes().iter() {
+        for entity in archetype.entities() {
+            let mesh_handle = match world.get::<Handle<Mesh>>(*entity) {
+                Some(mesh_handle) => mesh_handle,
+                None => continue,
+            };
+            let mut matrix = Mat4::IDENTITY;
+            let mut current = Some(*entity);
+            while let Some(node) = current {
+                matrix = world.get::<Transform>(node).map_or(Mat4::IDENTITY, |node_transform| node_transform.compute_matrix()) * matrix;
+                current = world.get::<Parent>(node).map(|parent| parent.get());
+            }
+            meshes_to_host.push((mesh_handle.clone_weak(), transform.compute_matrix() * matrix));
+        }
+    }
+    Some(meshes_to_host)
+}
@@ -1,14 +1,19 @@
-use std::f32::consts::PI;
-
 use crate::inputs::{CursorLockState};
 use crate::game_state::GameState;
-use crate::world::WorldState;
+use crate::movement::Mover;
+use crate::world::{DoorState,InventoryState,SoundsEvent,SoundsEventAction,
+    WorldFlagsState,WorldState,check_blockers};
 use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
 
 pub struct DoorStatePlugin;
 
 pub enum DoorEventAction {
     Toggle,
+    Open,
+    Close,
+    Lock,
+    Unlock,
 }
 
 pub struct DoorEvent {
@@ -23,6 +28,8 @@ impl Plugin for DoorStatePlugin {
         .add_system_set(
             SystemSet::on_update(GameState::Running)
             .with_system(update_door_interaction)
+            .with_system(update_door_behaviours.after(update_door_interaction))
+            .with_system(update_door_movement.after(update_door_behaviours))
         );
     }
 }
@@ -30,29 +37,212 @@ impl Plugin for DoorStatePlugin {
 
 fn update_door_interaction(
     cursor_lock_state: Res<CursorLockState>,
+    inventory_state: Res<InventoryState>,
+    world_flags_state: Res<WorldFlagsState>,
     mut world_state: ResMut<WorldState>,
     mut door_events: EventReader<DoorEvent>,
-    mut door_transforms: Query<(Entity, &mut Transform)>,
+    mut sounds_events: EventWriter<SoundsEvent>,
+    door_transforms: Query<&GlobalTransform>,
+    movers: Query<&GlobalTransform, With<Mover>>,
 ) {
     if !cursor_lock_state.enabled {
         return;
     }
 
+    let player_translation = movers.iter().next().map(|mover_transform| mover_transform.translation());
     for door_event in door_events.iter() {
         if let Some(door_state) = world_state.doors.get_mut(&door_event.door) {
-            let mut door_transform = door_transforms.get_mut(door_state.parent_entity.unwrap()).unwrap().1;
-            if matches!(door_event.action, DoorEventAction::Toggle) {
-                // todo fix door pivot
-                if door_state.open {
-                    let door_center = door_transform.translation.clone() - 0.5 * Vec3::X;
-                    door_transform.rotate_around(door_center, Quat::from_rotation_y(-3.*PI/2.));
-                    door_state.open = false;
-                } else {
-                    let door_center = door_transform.translation.clone() + 0.5 * Vec3::Z;
-                    door_transform.rotate_around(door_center, Quat::from_rotation_y(3.*PI/2.));
-                    door_state.open = true;
+            let open = match door_event.action {
+                DoorEventAction::Toggle => !door_state.open,
+                DoorEventAction::Open => true,
+                DoorEventAction::Close => false,
+                DoorEventAction::Lock => {
+                    door_state.locked = true;
+                    continue;
+                }
+                DoorEventAction::Unlock => {
+                    door_state.locked = false;
+                    continue;
+                }
+            };
+
+            if open && !door_state.open {
+                // a held key unlocks the door on the way through
+                if door_state.locked && door_key_held(door_state, &inventory_state, &world_flags_state) {
+                    door_state.locked = false;
+                }
+                // one-way doors only open for the player from their open side
+                let wrong_side = matches!(door_event.action, DoorEventAction::Toggle)
+                    && door_state.behaviour.one_way
+                    && player_translation.zip(door_transforms.get(door_state.parent_entity.unwrap()).ok())
+                        .is_some_and(|(player_translation, door_transform)|
+                            !door_on_open_side(door_state, door_transform, player_translation));
+                if door_state.locked || wrong_side {
+                    play_door_sound(&door_state.behaviour.locked_sound, door_translation(door_state, &door_transforms), &mut sounds_events);
+                    continue;
+                }
+            }
+            let translation = door_translation(door_state, &door_transforms);
+            set_door_open(door_state, open, translation, &mut sounds_events);
+        }
+    }
+}
+
+// opens proximity doors for the player and closes doors when their timer runs out
+fn update_door_behaviours(
+    time: Res<Time>,
+    cursor_lock_state: Res<CursorLockState>,
+    inventory_state: Res<InventoryState>,
+    world_flags_state: Res<WorldFlagsState>,
+    mut world_state: ResMut<WorldState>,
+    mut sounds_events: EventWriter<SoundsEvent>,
+    door_transforms: Query<&GlobalTransform>,
+    movers: Query<&GlobalTransform, With<Mover>>,
+) {
+    if !cursor_lock_state.enabled {
+        return;
+    }
+
+    let player_translation = movers.iter().next().map(|mover_transform| mover_transform.translation());
+    for door_state in world_state.doors.values_mut() {
+        let near = door_state.behaviour.proximity.is_some_and(|range| {
+            player_translation.zip(door_transforms.get(door_state.parent_entity.unwrap()).ok())
+                .is_some_and(|(player_translation, door_transform)|
+                    door_transform.translation().distance(player_translation) < range
+                    && (!door_state.behaviour.one_way
+                        || door_on_open_side(door_state, door_transform, player_translation)))
+        });
+
+        if near && !door_state.open {
+            // keycard doors unlock as the holder walks up
+            if door_state.locked && door_key_held(door_state, &inventory_state, &world_flags_state) {
+                door_state.locked = false;
+            }
+            if !door_state.locked {
+                let translation = door_translation(door_state, &door_transforms);
+                set_door_open(door_state, true, translation, &mut sounds_events);
+            }
+        }
+
+        // proximity doors stay open while the player is near
+        let auto_close = door_state.behaviour.auto_close.is_some() || door_state.behaviour.proximity.is_some();
+        if door_state.open && auto_close {
+            if near {
+                door_state.close_rmn = door_state.behaviour.auto_close.unwrap_or(0.0);
+            } else {
+                door_state.close_rmn -= time.delta_seconds();
+                if door_state.close_rmn <= 0.0 {
+                    let translation = door_translation(door_state, &door_transforms);
+                    set_door_open(door_state, false, translation, &mut sounds_events);
                 }
             }
         }
     }
 }
+
+fn set_door_open(
+    door_state: &mut DoorState,
+    open: bool,
+    translation: Option<Vec3>,
+    sounds_events: &mut EventWriter<SoundsEvent>,
+) {
+    if door_state.open == open {
+        return;
+    }
+    door_state.open = open;
+    if open {
+        door_state.close_rmn = door_state.behaviour.auto_close.unwrap_or(0.0);
+        play_door_sound(&door_state.behaviour.open_sound, translation, sounds_events);
+    } else {
+        play_door_sound(&door_state.behaviour.close_sound, translation, sounds_events);
+    }
+}
+
+fn door_translation(
+    door_state: &DoorState,
+    door_transforms: &Query<&GlobalTransform>,
+) -> Option<Vec3> {
+    door_state.parent_entity
+        .and_then(|parent_entity| door_transforms.get(parent_entity).ok())
+        .map(|door_transform| door_transform.translation())
+}
+
+fn play_door_sound(
+    sound: &Option<String>,
+    translation: Option<Vec3>,
+    sounds_events: &mut EventWriter<SoundsEvent>,
+) {
+    if let Some(sound) = sound {
+        sounds_events.send(SoundsEvent {
+            action: SoundsEventAction::PlayOnce(sound.clone(), translation),
+            name: "doors".into(),
+        });
+    }
+}
+
+fn door_key_held(
+    door_state: &DoorState,
+    inventory_state: &InventoryState,
+    world_flags_state: &WorldFlagsState,
+) -> bool {
+    door_state.behaviour.key.as_ref().is_some_and(|key| {
+        check_blockers(vec![(key.clone(), String::new())], inventory_state, world_flags_state).is_empty()
+    })
+}
+
+// whether the player stands on the side a one-way door opens from
+fn door_on_open_side(
+    door_state: &DoorState,
+    door_transform: &GlobalTransform,
+    player_translation: Vec3,
+) -> bool {
+    let (_scale, rotation, translation) = door_transform.to_scale_rotation_translation();
+    let parent_rotation = rotation * Quat::from_axis_angle(door_state.hinge_axis, door_state.swing).inverse();
+    (player_translation - translation).dot(parent_rotation * door_state.open_side) >= 0.0
+}
+
+// swings doors about their hinge towards open or closed
+fn update_door_movement(
+    time: Res<Time>,
+    cursor_lock_state: Res<CursorLockState>,
+    rapier_context: Res<RapierContext>,
+    mut world_state: ResMut<WorldState>,
+    mut door_transforms: Query<&mut Transform>,
+    movers: Query<Entity, With<Mover>>,
+) {
+    if !cursor_lock_state.enabled {
+        return;
+    }
+
+    for door_state in world_state.doors.values_mut() {
+        let mut target = if door_state.open { door_state.swing_angle } else { 0.0 };
+        if (target - door_state.swing).abs() < f32::EPSILON {
+            continue;
+        }
+
+        // a door pushing into the player holds while opening, or swings back open when closing
+        let blocked = door_state.collider_entity.is_some_and(|collider_entity| {
+            movers.iter().any(|mover_entity| rapier_context.contact_pair(collider_entity, mover_entity)
+                .is_some_and(|contact_pair| contact_pair.has_any_active_contacts()))
+        });
+        if blocked {
+            if door_state.open {
+                continue;
+            }
+            door_state.open = true;
+            door_state.close_rmn = door_state.behaviour.auto_close.unwrap_or(0.0);
+            target = door_state.swing_angle;
+        }
+
+        let step = door_state.swing_speed * time.delta_seconds();
+        door_state.swing += (target - door_state.swing).clamp(-step, step);
+
+        if let Ok(mut door_transform) = door_transforms.get_mut(door_state.parent_entity.unwrap()) {
+            let mut swung_transform = door_state.closed_transform;
+            swung_transform.rotate_around(
+                door_state.closed_transform.translation + door_state.hinge_pivot,
+                Quat::from_axis_angle(door_state.hinge_axis, door_state.swing));
+            *door_transform = swung_transform;
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use crate::inputs::{CursorLockState,MouseCamera};
+use crate::game_state::GameState;
+use crate::movement::{MovementState,Mover};
+use crate::world::{MixerState,WorldEchoes,WorldEchoZone,WorldState,listener_position};
+use bevy::prelude::*;
+use bevy_kira_audio::prelude::*;
+
+// early reflections per sound, each one this share of the last
+const REFLECTION_TAPS: u32 = 4;
+const REFLECTION_DECAY: f32 = 0.6;
+// echoes stop once this quiet, or after this many repeats
+const ECHO_MIN_VOLUME: f32 = 0.05;
+const ECHO_MAX_REPEATS: u32 = 6;
+
+pub struct EchoesStatePlugin;
+
+// a delayed copy of a one-shot sound, looped channels get no reflections
+pub struct SoundReflection {
+    pub rmn: f32,
+    pub sound: String,
+    pub volume: f32,
+    pub panning: f32,
+}
+
+// system state
+#[derive(Default)]
+pub struct EchoesState {
+    // blended at the listener
+    pub echoes: WorldEchoes,
+    pub reflections: Vec<SoundReflection>,
+}
+
+impl EchoesState {
+    // queues the reflections and echoes of a one-shot sound just played
+    pub fn reflect(
+        &mut self,
+        sound: &str,
+        volume: f32,
+        panning: f32,
+    ) {
+        let echoes = &self.echoes;
+        let mut reflections: Vec<SoundReflection> = Vec::new();
+        if echoes.reflections > f32::EPSILON {
+            for tap in 0..REFLECTION_TAPS {
+                // reflections come back from both sides, so they sit nearer the middle
+                let spread = if tap % 2 == 0 { 0.5 } else { -0.5 };
+                reflections.push(SoundReflection {
+                    rmn: echoes.reflection_spacing * (tap + 1) as f32,
+                    sound: sound.to_string(),
+                    volume: volume * echoes.reflections * REFLECTION_DECAY.powi(tap as i32),
+                    panning: 0.5 + (panning - 0.5) * spread,
+                });
+            }
+        }
+        if echoes.echo_delay > f32::EPSILON {
+            let mut echo_volume = volume;
+            for repeat in 1..=ECHO_MAX_REPEATS {
+                echo_volume *= echoes.echo_feedback.clamp(0.0, 0.95);
+                if echo_volume < ECHO_MIN_VOLUME {
+                    break;
+                }
+                reflections.push(SoundReflection {
+                    rmn: echoes.echo_delay * repeat as f32,
+                    sound: sound.to_string(),
+                    volume: echo_volume,
+                    panning,
+                });
+            }
+        }
+        self.reflections.extend(reflections);
+    }
+}
+
+impl Plugin for EchoesStatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<EchoesState>()
+        .add_system_set(
+            SystemSet::on_enter(GameState::Running)
+            .with_system(setup_echoes)
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Running)
+            .with_system(update_echoes)
+            .with_system(update_reflections)
+        );
+    }
+}
+
+// reflections play through the sfx bus, at the volume worked out for each
+fn setup_echoes(
+    mut audio: ResMut<DynamicAudioChannels>,
+    mut mixer_state: ResMut<MixerState>,
+) {
+    if !audio.is_channel("reflections") {
+        audio.create_channel("reflections");
+    }
+    mixer_state.route("reflections", "sfx");
+    mixer_state.set_channel_volume(&audio, "reflections", 1.0);
+}
+
+fn lerp_echoes(
+    from: &WorldEchoes,
+    to: &WorldEchoes,
+    t: f32,
+) -> WorldEchoes {
+    WorldEchoes {
+        reflections: from.reflections + (to.reflections - from.reflections) * t,
+        reflection_spacing: from.reflection_spacing + (to.reflection_spacing - from.reflection_spacing) * t,
+        echo_delay: from.echo_delay + (to.echo_delay - from.echo_delay) * t,
+        echo_feedback: from.echo_feedback + (to.echo_feedback - from.echo_feedback) * t,
+    }
+}
+
+// 1.0 inside the zone, fading out over its blend distance
+fn zone_weight(
+    zone: &WorldEchoZone,
+    position: Vec3,
+) -> f32 {
+    let outside = ((position - zone.translation).abs() - zone.scale).max(Vec3::ZERO).length();
+    if zone.blend > f32::EPSILON {
+        (1.0 - outside / zone.blend).clamp(0.0, 1.0)
+    } else if outside > 0.0 {
+        0.0
+    } else {
+        1.0
+    }
+}
+
+// blends zone echoes at the listener, smaller zones over the larger ones around them
+fn update_echoes(
+    cursor_lock_state: Res<CursorLockState>,
+    movement_state: Res<MovementState>,
+    world_state: Res<WorldState>,
+    mut echoes_state: ResMut<EchoesState>,
+    camera_query: Query<&GlobalTransform, With<MouseCamera>>,
+    mover_query: Query<(&GlobalTransform, &Mover)>,
+) {
+    if !cursor_lock_state.enabled {
+        return;
+    }
+
+    let (mover_transform, mover) = mover_query.single();
+    let listener = listener_position(&movement_state, camera_query.single(), mover_transform, mover);
+
+    let mut zones: Vec<(&WorldEchoZone, f32)> = world_state.echo_zones.iter()
+        .map(|zone| (zone, zone_weight(zone, listener)))
+        .filter(|(_, weight)| *weight > 0.0)
+        .collect();
+    zones.sort_by(|(a, _), (b, _)| (b.scale.x * b.scale.y * b.scale.z).total_cmp(&(a.scale.x * a.scale.y * a.scale.z)));
+    let echoes = zones.iter().fold(world_state.echoes.clone(), |echoes, (zone, weight)| {
+        lerp_echoes(&echoes, &zone.echoes, *weight)
+    });
+    echoes_state.echoes = echoes;
+}
+
+fn update_reflections(
+    time: Res<Time>,
+    asset_server: Res<AssetServer>,
+    audio: Res<DynamicAudioChannels>,
+    mut echoes_state: ResMut<EchoesState>,
+) {
+    if echoes_state.reflections.is_empty() {
+        return;
+    }
+    let dt = time.delta_seconds();
+    let channel = audio.channel("reflections");
+    echoes_state.reflections.retain_mut(|reflection| {
+        reflection.rmn -= dt;
+        if reflection.rmn > 0.0 {
+            return true;
+        }
+        channel.play(asset_server.load(&format!("audio/{}.ogg", reflection.sound)))
+            .with_volume(reflection.volume as f64)
+            .with_panning(reflection.panning as f64);
+        false
+    });
+}
@@ -0,0 +1,175 @@
+use crate::inputs::{CursorLockState};
+use crate::game_state::GameState;
+use crate::world::{LightsEvent,LightsEventAction,WorldEntity,WorldEnvironment,WorldState};
+use bevy::prelude::*;
+use bevy::render::camera::Projection;
+use std::f32::consts::TAU;
+
+// seconds for dusk lights to warm up or die down
+const DUSK_LIGHT_FADE: f32 = 2.0;
+
+pub struct EnvironmentStatePlugin;
+
+// system state
+#[derive(Default)]
+pub struct EnvironmentState {
+    pub environment: WorldEnvironment,
+    pub sun: Option<Entity>,
+    // hours, 0.0 to 24.0
+    pub time_of_day: f32,
+    // last dusk state sent to lights
+    pub night: Option<bool>,
+}
+
+impl EnvironmentState {
+    // sun direction for the time of day, turning about the x axis through noon
+    fn sun_direction(&self, noon: Vec3) -> Vec3 {
+        match self.environment.day_night {
+            Some(_) => Quat::from_rotation_x((self.time_of_day / 24.0 - 0.5) * TAU) * noon.normalize(),
+            None => noon.normalize(),
+        }
+    }
+
+    // 1.0 through the day, easing to 0.0 over the hour around dusk and dawn
+    fn daylight(&self) -> f32 {
+        match &self.environment.day_night {
+            Some(day_night) => {
+                (self.time_of_day - day_night.dawn + 0.5).clamp(0.0, 1.0)
+                    * (day_night.dusk + 0.5 - self.time_of_day).clamp(0.0, 1.0)
+            }
+            None => 1.0,
+        }
+    }
+}
+
+impl Plugin for EnvironmentStatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .insert_resource(ClearColor(WorldEnvironment::default().clear_color))
+        .init_resource::<EnvironmentState>()
+        .add_system_set(
+            SystemSet::on_update(GameState::Running)
+            .with_system(update_environment_time)
+            // applied while the cursor is free so the world never shows the defaults
+            .with_system(update_environment.after(update_environment_time))
+        );
+    }
+}
+
+// resets the environment for a loading world and spawns its sun
+pub fn setup_environment(
+    commands: &mut Commands,
+    environment_state: &mut EnvironmentState,
+    environment: &WorldEnvironment,
+) {
+    *environment_state = EnvironmentState {
+        environment: environment.clone(),
+        time_of_day: environment.day_night.as_ref().map_or(12.0, |day_night| day_night.start),
+        ..default()
+    };
+    if let Some(sun) = &environment.sun {
+        environment_state.sun = Some(commands.spawn_bundle(DirectionalLightBundle {
+            transform: sun_transform(environment_state.sun_direction(sun.direction)),
+            directional_light: DirectionalLight {
+                color: sun.color,
+                illuminance: sun.illuminance,
+                shadows_enabled: sun.shadows,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(WorldEntity)
+        .id());
+    }
+}
+
+// directional lights shine along -Z
+fn sun_transform(
+    direction: Vec3,
+) -> Transform {
+    Transform::from_rotation(Quat::from_rotation_arc(-Vec3::Z, direction))
+}
+
+fn lerp_color(
+    from: Color,
+    to: Color,
+    t: f32,
+) -> Color {
+    let (from, to) = (Vec4::from(from.as_rgba_f32()), Vec4::from(to.as_rgba_f32()));
+    let color = from.lerp(to, t);
+    Color::rgba(color.x, color.y, color.z, color.w)
+}
+
+// advances the clock and switches dusk lights as night falls and lifts
+fn update_environment_time(
+    time: Res<Time>,
+    cursor_lock_state: Res<CursorLockState>,
+    world_state: Res<WorldState>,
+    mut environment_state: ResMut<EnvironmentState>,
+    mut lights_events: EventWriter<LightsEvent>,
+) {
+    if !cursor_lock_state.enabled {
+        return;
+    }
+
+    let (period, dusk, dawn) = match &environment_state.environment.day_night {
+        Some(day_night) => (day_night.period, day_night.dusk, day_night.dawn),
+        None => return,
+    };
+    environment_state.time_of_day = (environment_state.time_of_day
+        + 24.0 * time.delta_seconds() / period.max(f32::EPSILON)).rem_euclid(24.0);
+
+    let night = environment_state.time_of_day >= dusk || environment_state.time_of_day < dawn;
+    if environment_state.night != Some(night) {
+        environment_state.night = Some(night);
+        for (name, light_state) in world_state.animatable_lights.iter() {
+            if light_state.dusk {
+                lights_events.send(LightsEvent {
+                    action: LightsEventAction::Fade(night, DUSK_LIGHT_FADE),
+                    name: name.clone(),
+                });
+            }
+        }
+    }
+}
+
+fn update_environment(
+    environment_state: Res<EnvironmentState>,
+    mut clear_color: ResMut<ClearColor>,
+    mut ambient_light: ResMut<AmbientLight>,
+    mut suns: Query<(&mut DirectionalLight, &mut Transform)>,
+    mut projections: Query<&mut Projection, With<Camera3d>>,
+) {
+    if !environment_state.is_changed() {
+        return;
+    }
+
+    let environment = &environment_state.environment;
+    let daylight = environment_state.daylight();
+
+    let far = environment.view_distance.unwrap_or(PerspectiveProjection::default().far);
+    for mut projection in projections.iter_mut() {
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.far = far;
+        }
+    }
+
+    match &environment.day_night {
+        Some(day_night) => {
+            clear_color.0 = lerp_color(day_night.night_clear_color, environment.clear_color, daylight);
+            ambient_light.brightness = environment.ambient_brightness * (day_night.night_ambient + (1.0 - day_night.night_ambient) * daylight);
+        }
+        None => {
+            clear_color.0 = environment.clear_color;
+            ambient_light.brightness = environment.ambient_brightness;
+        }
+    }
+    ambient_light.color = environment.ambient_color;
+
+    if let Some(sun) = &environment.sun {
+        if let Some((mut sun_light, mut transform)) = environment_state.sun.and_then(|entity| suns.get_mut(entity).ok()) {
+            sun_light.illuminance = sun.illuminance * daylight;
+            *transform = sun_transform(environment_state.sun_direction(sun.direction));
+        }
+    }
+}
@@ -4,9 +4,10 @@ use crate::loading::{FontAssets};
 use crate::movement::{MovementState,Mover,MoverParent};
 use crate::world::{AnimatableEvent,AnimatableEventAction,DoorEvent,
     DoorEventAction,InteractableState,InventoryEvent,InventoryEventAction,
-    InventoryItem,InventoryState,LightsEvent,LightsEventAction,
-    SoundsEvent,SoundsEventAction,TrainsEvent,TrainsEventAction,
+    InventoryItem,InventoryState,LightsEvent,LightsEventAction,MoversEvent,
+    MoversEventAction,RailsEvent,RailsEventAction,SoundsEvent,SoundsEventAction,TrainsEvent,TrainsEventAction,
     WorldFlagsEvent,WorldFlagsEventAction,WorldFlagsState,WorldState};
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
 
@@ -28,6 +29,20 @@ pub struct InteractablesState {
 #[derive(Component)]
 struct InteractablesOverlayText;
 
+// event writers for interaction actions
+#[derive(SystemParam)]
+struct InteractionEvents<'w, 's> {
+    animatable_events: EventWriter<'w, 's, AnimatableEvent>,
+    door_events: EventWriter<'w, 's, DoorEvent>,
+    inventory_events: EventWriter<'w, 's, InventoryEvent>,
+    lights_events: EventWriter<'w, 's, LightsEvent>,
+    movers_events: EventWriter<'w, 's, MoversEvent>,
+    rails_events: EventWriter<'w, 's, RailsEvent>,
+    sounds_events: EventWriter<'w, 's, SoundsEvent>,
+    trains_events: EventWriter<'w, 's, TrainsEvent>,
+    world_flags_events: EventWriter<'w, 's, WorldFlagsEvent>,
+}
+
 pub struct InteractableStatePlugin;
 
 impl Plugin for InteractableStatePlugin {
@@ -199,7 +214,7 @@ fn update_interactable_interaction(
         if let Some(interactable) = &interactables_state.active_interactable {
             // check blockers
             let blockers = check_blockers(interactable.interaction.blockers.clone(),
-                inventory_state, world_flags_state);
+                &inventory_state, &world_flags_state);
 
             if let Some(first_blocker) = blockers.first() {
                 // show blocker text
@@ -224,18 +239,13 @@ fn update_mouse_click_interaction(
     cursor_lock_state: Res<CursorLockState>,
     mouse_button_input: Res<Input<MouseButton>>,
     mut movement_state: ResMut<MovementState>,
-    mut animatable_events: EventWriter<AnimatableEvent>,
-    mut door_events: EventWriter<DoorEvent>,
-    mut inventory_events: EventWriter<InventoryEvent>,
-    mut lights_events: EventWriter<LightsEvent>,
-    mut sounds_events: EventWriter<SoundsEvent>,
-    mut trains_events: EventWriter<TrainsEvent>,
-    mut world_flags_events: EventWriter<WorldFlagsEvent>,
-    // audio_assets: Res<AudioAssets>,
+    mut events: InteractionEvents,
     mut interactables_state: ResMut<InteractablesState>,
     inventory_state: Res<InventoryState>,
     world_flags_state: Res<WorldFlagsState>,
+    world_state: Res<WorldState>,
     time: Res<Time>,
+    transforms: Query<&GlobalTransform>,
 ) {
     if !cursor_lock_state.enabled {
         return;
@@ -249,46 +259,126 @@ fn update_mouse_click_interaction(
     if mouse_button_input.just_pressed(MouseButton::Left) && interactables_state.blocked_rmn <= 0.0001 {
         if let Some(interactable) = &interactables_state.active_interactable {
 
+            // sounds come from the interactable
+            let position = interactables_state.active_interactable_entity
+                .and_then(|entity| transforms.get(entity).ok())
+                .map(|transform| transform.translation());
+            let feedback = &interactable.interaction.feedback;
+
             // check blockers
             let blockers = check_blockers(interactable.interaction.blockers.clone(),
-                inventory_state, world_flags_state);
+                &inventory_state, &world_flags_state);
             if blockers.len() > 0 {
+                let blocked_sound = feedback.blocked_sound.as_ref().or(world_state.feedback_sounds.blocked.as_ref());
+                if let Some(sound) = blocked_sound.filter(|_| !feedback.mute) {
+                    events.sounds_events.send(SoundsEvent {
+                        action: SoundsEventAction::PlayOnce(sound.clone(), position),
+                        name: "effects".into(),
+                    });
+                }
                 return;
             }
 
+            // feedback for the interaction, unless it plays its own sound
+            let plays_sound = interactable.interaction.actions.iter().any(|action| action.0 == "audio_playonce");
+            let feedback_sound = feedback.sound.clone().or_else(|| if plays_sound { None } else {
+                interactable.interaction.actions.iter().find_map(|action| match action.0.as_str() {
+                    "pickup_item" => world_state.feedback_sounds.pickup.clone(),
+                    "toggle_light" | "set_light" | "fade_light" | "set_color" => world_state.feedback_sounds.light_switch.clone(),
+                    _ => None,
+                })
+            });
+            if let Some(sound) = feedback_sound.filter(|_| !feedback.mute) {
+                events.sounds_events.send(SoundsEvent {
+                    action: SoundsEventAction::PlayOnce(sound, position),
+                    name: "effects".into(),
+                });
+            }
+
             // send action events
             for action in interactable.interaction.actions.iter() {
                 match action.0.as_str() {
-                    // "audio_playonce" => {
-                    //     audio_events.send(AudioEvent {
-                    //         action: AudioEventAction::PlayOnce,
-                    //         source: Some(audio_assets.big_switch.clone()),
-                    //     });
-                    // },
+                    "audio_playonce" => {
+                        events.sounds_events.send(SoundsEvent {
+                            action: SoundsEventAction::PlayOnce(action.1.clone(), position),
+                            name: "effects".into(),
+                        });
+                    },
                     "animate" => {
                         let parts = action.1.split(".").collect::<Vec<&str>>();
                         let animatable_name = parts[0].to_string();
                         let animation_name = parts[1].to_string();
-                        animatable_events.send(AnimatableEvent {
+                        events.animatable_events.send(AnimatableEvent {
                             action: AnimatableEventAction::PlayOnce,
                             name: animatable_name,
                             animation: animation_name,
                         });
                     },
                     "toggle_door" => {
-                        door_events.send(DoorEvent {
+                        events.door_events.send(DoorEvent {
                             action: DoorEventAction::Toggle,
                             door: action.1.to_string(),
                         });
                     },
+                    "lock_door" => {
+                        events.door_events.send(DoorEvent {
+                            action: DoorEventAction::Lock,
+                            door: action.1.to_string(),
+                        });
+                    },
+                    "unlock_door" => {
+                        events.door_events.send(DoorEvent {
+                            action: DoorEventAction::Unlock,
+                            door: action.1.to_string(),
+                        });
+                    },
                     "toggle_light" => {
-                        lights_events.send(LightsEvent {
+                        events.lights_events.send(LightsEvent {
                             action: LightsEventAction::Toggle,
                             name: action.1.to_string(),
                         });
                     },
+                    "set_light" => {
+                        // name.on or name.off
+                        let parts = action.1.split(".").collect::<Vec<&str>>();
+                        match parts.get(1).copied() {
+                            Some("on") | Some("off") => {
+                                events.lights_events.send(LightsEvent {
+                                    action: LightsEventAction::Set(parts[1] == "on"),
+                                    name: parts[0].to_string(),
+                                });
+                            },
+                            _ => println!("bad action {:?}", action),
+                        }
+                    },
+                    "fade_light" => {
+                        // name.on.seconds or name.off.seconds
+                        let parts = action.1.splitn(3, ".").collect::<Vec<&str>>();
+                        let seconds = parts.get(2).and_then(|seconds| seconds.parse::<f32>().ok());
+                        match (parts.get(1).copied(), seconds) {
+                            (Some("on"), Some(seconds)) | (Some("off"), Some(seconds)) => {
+                                events.lights_events.send(LightsEvent {
+                                    action: LightsEventAction::Fade(parts[1] == "on", seconds),
+                                    name: parts[0].to_string(),
+                                });
+                            },
+                            _ => println!("bad action {:?}", action),
+                        }
+                    },
+                    "set_color" => {
+                        // name.rrggbb
+                        let parts = action.1.split(".").collect::<Vec<&str>>();
+                        if let Some(color) = parts.get(1).and_then(|color| Color::hex(color).ok()) {
+                            events.lights_events.send(LightsEvent {
+                                action: LightsEventAction::SetColor(color),
+                                name: parts[0].to_string(),
+                            });
+                        } else {
+                            println!("bad action {:?}", action);
+                        }
+                    },
                     "toggle_sound" => {
-                        sounds_events.send(SoundsEvent {
+                        events.sounds_events.send(SoundsEvent {
                             action: SoundsEventAction::Toggle,
                             name: action.1.to_string(),
                         });
@@ -301,7 +391,7 @@ fn update_mouse_click_interaction(
                                 InventoryItem::FuseSmall
                             },
                         };
-                        inventory_events.send(InventoryEvent {
+                        events.inventory_events.send(InventoryEvent {
                             action: InventoryEventAction::RemoveItem,
                             item,
                         });
@@ -315,30 +405,71 @@ fn update_mouse_click_interaction(
                                 InventoryItem::FuseSmall
                             },
                         };
-                        inventory_events.send(InventoryEvent {
+                        events.inventory_events.send(InventoryEvent {
                             action: InventoryEventAction::AddItem,
                             item,
                         });
                     },
                     "enable_flag" => {
-                        world_flags_events.send(WorldFlagsEvent {
+                        events.world_flags_events.send(WorldFlagsEvent {
                             action: WorldFlagsEventAction::Enable,
                             flag: action.1.clone(),
                         });
                     },
                     "hide_prop" => {
-                        animatable_events.send(AnimatableEvent {
+                        events.animatable_events.send(AnimatableEvent {
                             action: AnimatableEventAction::Despawn,
                             name: action.1.clone(),
                             animation: "".to_string(),
                         });
                     },
                     "train_control" => {
-                        trains_events.send(TrainsEvent {
+                        events.trains_events.send(TrainsEvent {
                             action: TrainsEventAction::StartControl,
                             train: action.1.clone(),
                         });
                     },
+                    "couple_car" => {
+                        events.trains_events.send(TrainsEvent {
+                            action: TrainsEventAction::Couple(
+                                interactables_state.active_interactable_entity.unwrap()),
+                            train: action.1.clone(),
+                        });
+                    },
+                    "uncouple_car" => {
+                        events.trains_events.send(TrainsEvent {
+                            action: TrainsEventAction::Uncouple(
+                                interactables_state.active_interactable_entity.unwrap()),
+                            train: action.1.clone(),
+                        });
+                    },
+                    "mover_start" | "mover_stop" | "mover_toggle" => {
+                        events.movers_events.send(MoversEvent {
+                            action: match action.0.as_str() {
+                                "mover_start" => MoversEventAction::Start,
+                                "mover_stop" => MoversEventAction::Stop,
+                                _ => MoversEventAction::Toggle,
+                            },
+                            name: action.1.clone(),
+                        });
+                    },
+                    "mover_goto" => {
+                        let parts = action.1.split(".").collect::<Vec<&str>>();
+                        if let Some(waypoint) = parts.get(1).and_then(|waypoint| waypoint.parse::<usize>().ok()) {
+                            events.movers_events.send(MoversEvent {
+                                action: MoversEventAction::GoTo(waypoint),
+                                name: parts[0].to_string(),
+                            });
+                        } else {
+                            println!("bad action {:?}", action);
+                        }
+                    },
+                    "throw_switch" => {
+                        events.rails_events.send(RailsEvent {
+                            action: RailsEventAction::ThrowSwitch,
+                            name: action.1.clone(),
+                        });
+                    },
                     "despawn_self" => {
                         commands.entity(
                             interactables_state.active_interactable_entity.unwrap()).despawn();
@@ -357,10 +488,11 @@ fn update_mouse_click_interaction(
     }
 }
 
-fn check_blockers(
+// blockers not satisfied by inventory or world flags
+pub fn check_blockers(
     blockers: Vec<(String, String)>,
-    inventory_state: Res<InventoryState>,
-    world_flags_state: Res<WorldFlagsState>,
+    inventory_state: &InventoryState,
+    world_flags_state: &WorldFlagsState,
 ) -> Vec<(String,String)> {
     blockers.clone().into_iter().filter({|blocker|
         if blocker.0.starts_with("holding") {
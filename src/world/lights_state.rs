@@ -1,12 +1,27 @@
 use crate::inputs::{CursorLockState};
 use crate::game_state::GameState;
-use crate::world::WorldState;
+use crate::world::{LightBehaviourState,LightEmissiveState,MoversState,WorldFlagsState,WorldLight,WorldLightBehaviour,WorldLightState,WorldState};
+use bevy::asset::HandleId;
+use bevy::gltf::Gltf;
 use bevy::prelude::*;
+use bevy::scene::InstanceId;
+use rand::{Rng,SeedableRng};
+use rand::rngs::StdRng;
+use std::collections::{HashMap,HashSet};
+use std::collections::hash_map::Entry;
+use std::f32::consts::TAU;
+
+// intensity for lights placed switched off without on_watts
+const LIGHT_DEFAULT_ON_WATTS: f32 = 50.;
 
 pub struct LightsStatePlugin;
 
 pub enum LightsEventAction {
     Toggle,
+    Set(bool),
+    // switch on or off over seconds
+    Fade(bool, f32),
+    SetColor(Color),
 }
 
 pub struct LightsEvent {
@@ -21,33 +36,414 @@ impl Plugin for LightsStatePlugin {
         .add_system_set(
             SystemSet::on_update(GameState::Running)
             .with_system(update_light_interaction)
+            .with_system(update_light_behaviours)
+            .with_system(update_light_emissives)
+            .with_system(update_light_levels.after(update_light_interaction).after(update_light_behaviours)
+                .after(update_light_emissives))
         );
     }
 }
 
+// light state for a loaded light, on when placed with watts
+// emissive_instance is the spawned scene of the lamp prop
+pub fn light_state(
+    data: &WorldLight,
+    entity: Entity,
+    emissive_instance: Option<InstanceId>,
+) -> WorldLightState {
+    let on = data.watts > 0.0;
+    WorldLightState {
+        entity: Some(entity),
+        on,
+        intensity: data.on_watts.unwrap_or(if on { data.watts } else { LIGHT_DEFAULT_ON_WATTS }),
+        color: Color::WHITE,
+        level: if on { 1.0 } else { 0.0 },
+        fade_target: if on { 1.0 } else { 0.0 },
+        fade_rate: 0.0,
+        behaviour: data.behaviour.as_ref().map(|behaviour| light_behaviour_state(behaviour, data.translation)),
+        emissive: data.emissive.as_ref().map(|emissive| LightEmissiveState {
+            instance: emissive_instance,
+            node: emissive.node.clone(),
+            material: emissive.material.clone(),
+            strength: emissive.strength,
+            materials: Vec::new(),
+            resolved: false,
+            color: Color::NONE,
+        }),
+        dusk: data.dusk,
+    }
+}
 
 fn update_light_interaction(
     cursor_lock_state: Res<CursorLockState>,
     mut world_state: ResMut<WorldState>,
     mut lights_events: EventReader<LightsEvent>,
-    mut point_lights: Query<&mut PointLight>,
 ) {
     if !cursor_lock_state.enabled {
         return;
     }
 
     for lights_event in lights_events.iter() {
-        if let Some(light_entity) = world_state.animatable_lights.get_mut(&lights_event.name) {
+        if let Some(light_state) = world_state.animatable_lights.get_mut(&lights_event.name) {
             match lights_event.action {
                 LightsEventAction::Toggle => {
-                    let mut point_light = point_lights.get_mut(*light_entity).unwrap();
-                    if point_light.intensity <= 0.0001 {
-                        point_light.intensity = 50.;
+                    set_light_on(light_state, !light_state.on, 0.0);
+                }
+                LightsEventAction::Set(on) => {
+                    set_light_on(light_state, on, 0.0);
+                }
+                LightsEventAction::Fade(on, seconds) => {
+                    set_light_on(light_state, on, seconds);
+                }
+                LightsEventAction::SetColor(color) => {
+                    light_state.color = color;
+                }
+            }
+        } else {
+            println!("unknown light :: {:?}", lights_event.name);
+        }
+    }
+}
+
+fn set_light_on(
+    light_state: &mut WorldLightState,
+    on: bool,
+    seconds: f32,
+) {
+    light_state.on = on;
+    light_state.fade_target = if on { 1.0 } else { 0.0 };
+    if seconds > f32::EPSILON {
+        light_state.fade_rate = 1.0 / seconds;
+    } else {
+        light_state.level = light_state.fade_target;
+    }
+}
+
+impl LightBehaviourState {
+    // advances the pattern by dt seconds, returns the lit share of intensity
+    // idle patterns sit fully lit
+    pub fn step(&mut self, dt: f32) -> f32 {
+        if !self.active {
+            self.level = 1.0;
+            self.target = 1.0;
+            return self.level;
+        }
+
+        self.phase = (self.phase + self.rate * dt).fract();
+        self.hold -= dt;
+        match self.pattern.as_str() {
+            "flicker" => {
+                // mostly lit, with short random dips
+                if self.hold <= 0.0 {
+                    if self.level >= 1.0 && self.rng.gen_bool(0.3) {
+                        self.level = self.rng.gen_range(self.min..=1.0);
+                        self.hold = self.rng.gen_range(0.02..0.15);
+                    } else {
+                        self.level = 1.0;
+                        self.hold = self.rng.gen_range(0.2..1.8) / self.rate;
+                    }
+                }
+            }
+            "strobe" => {
+                self.level = if self.phase < 0.5 { 1.0 } else { self.min };
+            }
+            "pulse" => {
+                self.level = self.min + (1.0 - self.min) * (0.5 - 0.5 * (TAU * self.phase).cos());
+            }
+            "brown_out" => {
+                if !self.powered {
+                    self.level = 0.0;
+                    self.target = 0.0;
+                    self.hold = 0.0;
+                } else if !self.overloaded {
+                    self.target = 1.0;
+                } else if self.hold <= 0.0 {
+                    // sagging supply, wandering dim with the odd dropout
+                    if self.rng.gen_bool(0.1) {
+                        self.level = 0.0;
+                        self.target = 0.0;
+                        self.hold = self.rng.gen_range(0.05..0.3);
                     } else {
-                        point_light.intensity = 0.;
+                        self.target = self.rng.gen_range(self.min..=(0.5 + 0.5 * self.min));
+                        self.hold = self.rng.gen_range(0.5..2.0) / self.rate;
                     }
                 }
+                let step = self.rate * dt;
+                self.level += (self.target - self.level).clamp(-step, step);
+            }
+            "steady" => {}
+            _ => {
+                println!("unknown light pattern :: {:?}", self.pattern);
+                self.level = 1.0;
+                self.pattern = "steady".into();
             }
         }
+        self.level
+    }
+}
+
+// pattern state for a light, seeded from its position unless the world sets a seed
+pub fn light_behaviour_state(
+    behaviour: &WorldLightBehaviour,
+    translation: Vec3,
+) -> LightBehaviourState {
+    LightBehaviourState {
+        pattern: behaviour.pattern.clone(),
+        rate: behaviour.rate.max(f32::EPSILON),
+        min: behaviour.min.clamp(0.0, 1.0),
+        flag: behaviour.flag.clone(),
+        power: behaviour.power.clone(),
+        rng: StdRng::seed_from_u64(behaviour.seed.unwrap_or_else(|| light_seed(translation))),
+        active: true,
+        powered: true,
+        overloaded: false,
+        phase: 0.0,
+        hold: 0.0,
+        level: 1.0,
+        target: 1.0,
+    }
+}
+
+// the same from run to run, unlike entity ids
+fn light_seed(
+    translation: Vec3,
+) -> u64 {
+    translation.to_array().iter()
+        .fold(0, |seed, value| seed.rotate_left(21) ^ value.to_bits() as u64)
+}
+
+// power networks drawn on past their capacity by lit lights and running movers
+fn overloaded_networks(
+    world_state: &WorldState,
+    movers_state: &MoversState,
+) -> HashSet<String> {
+    let mut loads: HashMap<&str, u32> = HashMap::new();
+    for light_state in world_state.animatable_lights.values().filter(|light_state| light_state.on) {
+        if let Some(power) = light_state.behaviour.as_ref().and_then(|behaviour| behaviour.power.as_ref()) {
+            *loads.entry(power).or_default() += 1;
+        }
+    }
+    for mover_state in movers_state.movers.values().filter(|mover_state| mover_state.running) {
+        if let Some(power) = mover_state.power.as_ref() {
+            *loads.entry(power).or_default() += 1;
+        }
+    }
+    world_state.power_networks.iter()
+        .filter(|network| loads.get(network.flag.as_str()).copied().unwrap_or(0) > network.capacity)
+        .map(|network| network.flag.clone())
+        .collect()
+}
+
+fn update_light_behaviours(
+    time: Res<Time>,
+    world_flags_state: Res<WorldFlagsState>,
+    movers_state: Res<MoversState>,
+    mut world_state: ResMut<WorldState>,
+) {
+    let dt = time.delta_seconds();
+    let overloaded = overloaded_networks(&world_state, &movers_state);
+    let enabled = |flag: &String| world_flags_state.flags.get(flag).copied().unwrap_or(false);
+    for behaviour in world_state.animatable_lights.values_mut().filter_map(|light_state| light_state.behaviour.as_mut()) {
+        behaviour.active = behaviour.flag.as_ref().is_none_or(enabled);
+        behaviour.powered = behaviour.power.as_ref().is_none_or(enabled);
+        behaviour.overloaded = behaviour.power.as_ref().is_some_and(|power| overloaded.contains(power));
+        behaviour.step(dt);
+    }
+}
+
+// swaps lamp materials for per light copies once their scene is ready
+fn update_light_emissives(
+    mut commands: Commands,
+    scene_spawner: Res<SceneSpawner>,
+    gltfs: Res<Assets<Gltf>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut world_state: ResMut<WorldState>,
+    names: Query<&Name>,
+    children: Query<&Children>,
+    material_handles: Query<&Handle<StandardMaterial>>,
+) {
+    for (light_name, light_state) in world_state.animatable_lights.iter_mut() {
+        let emissive = match light_state.emissive.as_mut() {
+            Some(emissive) if !emissive.resolved => emissive,
+            _ => continue,
+        };
+        let instance = match emissive.instance {
+            Some(instance) => instance,
+            None => {
+                println!("unknown light emissive prop :: {:?}", light_name);
+                emissive.resolved = true;
+                continue;
+            }
+        };
+        if !scene_spawner.instance_is_ready(instance) {
+            continue;
+        }
+        emissive.resolved = true;
+
+        // materials are matched by name through the loaded gltf files
+        let named_materials = emissive.material.as_ref().map_or(Vec::new(), |material| {
+            gltfs.iter()
+                .filter_map(|(_, gltf)| gltf.named_materials.get(material))
+                .map(|material_handle| material_handle.id)
+                .collect::<Vec<HandleId>>()
+        });
+        // nodes hold their mesh primitives as children
+        let mut targets: Vec<Entity> = Vec::new();
+        for entity in scene_spawner.iter_instance_entities(instance).into_iter().flatten() {
+            let named_node = emissive.node.as_ref()
+                .is_some_and(|node| names.get(entity).is_ok_and(|name| name.as_str() == node));
+            if named_node {
+                targets.push(entity);
+                if let Ok(node_children) = children.get(entity) {
+                    targets.extend(node_children.iter());
+                }
+            } else if material_handles.get(entity).is_ok_and(|material_handle| named_materials.contains(&material_handle.id)) {
+                targets.push(entity);
+            }
+        }
+
+        // copy so lamps sharing a material light up on their own
+        let mut copies: HashMap<HandleId, Handle<StandardMaterial>> = HashMap::new();
+        for entity in targets {
+            let material_handle = match material_handles.get(entity) {
+                Ok(material_handle) => material_handle,
+                Err(_) => continue,
+            };
+            let copy = match copies.entry(material_handle.id) {
+                Entry::Occupied(entry) => entry.get().clone(),
+                Entry::Vacant(entry) => match materials.get(material_handle).cloned() {
+                    Some(material) => entry.insert(materials.add(material)).clone(),
+                    None => continue,
+                },
+            };
+            commands.entity(entity).insert(copy);
+        }
+        if copies.is_empty() {
+            println!("unknown light emissive :: {:?} {:?} {:?}", light_name, emissive.node, emissive.material);
+        }
+        emissive.materials = copies.into_values().collect();
+    }
+}
+
+// fades lights and writes intensity and color to whichever light kind they are
+fn update_light_levels(
+    time: Res<Time>,
+    mut world_state: ResMut<WorldState>,
+    mut point_lights: Query<&mut PointLight>,
+    mut directional_lights: Query<&mut DirectionalLight>,
+    mut spot_lights: Query<&mut SpotLight>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for light_state in world_state.animatable_lights.values_mut() {
+        let step = light_state.fade_rate * time.delta_seconds();
+        light_state.level += (light_state.fade_target - light_state.level).clamp(-step, step);
+
+        let entity = match light_state.entity {
+            Some(entity) => entity,
+            None => continue,
+        };
+        let behaviour_level = light_state.behaviour.as_ref().map_or(1.0, |behaviour| behaviour.level);
+        let intensity = light_state.intensity * light_state.level * behaviour_level;
+
+        // lamp glow follows the lit share of intensity
+        if let Some(emissive) = light_state.emissive.as_mut() {
+            let emissive_color = light_state.color * (light_state.level * behaviour_level * emissive.strength);
+            if emissive.color != emissive_color && !emissive.materials.is_empty() {
+                emissive.color = emissive_color;
+                for material_handle in emissive.materials.iter() {
+                    if let Some(material) = materials.get_mut(material_handle) {
+                        material.emissive = emissive_color;
+                    }
+                }
+            }
+        }
+
+        if let Ok(mut point_light) = point_lights.get_mut(entity) {
+            if point_light.intensity != intensity || point_light.color != light_state.color {
+                point_light.intensity = intensity;
+                point_light.color = light_state.color;
+            }
+        } else if let Ok(mut directional_light) = directional_lights.get_mut(entity) {
+            if directional_light.illuminance != intensity || directional_light.color != light_state.color {
+                directional_light.illuminance = intensity;
+                directional_light.color = light_state.color;
+            }
+        } else if let Ok(mut spot_light) = spot_lights.get_mut(entity) {
+            if spot_light.intensity != intensity || spot_light.color != light_state.color {
+                spot_light.intensity = intensity;
+                spot_light.color = light_state.color;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn behaviour(pattern: &str, seed: Option<u64>) -> LightBehaviourState {
+        light_behaviour_state(&WorldLightBehaviour {
+            pattern: pattern.into(),
+            seed,
+            ..Default::default()
+        }, Vec3::new(1.0, 2.0, 3.0))
+    }
+
+    fn levels(behaviour: &mut LightBehaviourState, steps: usize) -> Vec<f32> {
+        (0..steps).map(|_| behaviour.step(1.0 / 60.0)).collect()
+    }
+
+    #[test]
+    fn same_seed_same_flicker() {
+        let first = levels(&mut behaviour("flicker", Some(7)), 600);
+        let second = levels(&mut behaviour("flicker", Some(7)), 600);
+        assert_eq!(first, second);
+        assert!(first.iter().any(|level| *level < 1.0));
+        assert_ne!(first, levels(&mut behaviour("flicker", Some(8)), 600));
+    }
+
+    #[test]
+    fn default_seed_follows_position() {
+        let first = levels(&mut behaviour("flicker", None), 600);
+        let second = levels(&mut behaviour("flicker", None), 600);
+        assert_eq!(first, second);
+        assert_ne!(light_seed(Vec3::new(1.0, 2.0, 3.0)), light_seed(Vec3::new(3.0, 2.0, 1.0)));
+    }
+
+    #[test]
+    fn strobe_and_pulse_follow_phase() {
+        let mut strobe = behaviour("strobe", None);
+        assert_eq!(strobe.step(0.05), 1.0);
+        assert_eq!(strobe.step(0.1), strobe.min);
+        let mut pulse = behaviour("pulse", None);
+        assert!((pulse.step(0.125) - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn brown_out_follows_power_network() {
+        let mut brown_out = behaviour("brown_out", Some(3));
+        brown_out.powered = false;
+        assert_eq!(brown_out.step(1.0 / 60.0), 0.0);
+
+        // eases back to fully lit on a network within capacity
+        brown_out.powered = true;
+        assert!(levels(&mut brown_out, 60).last().is_some_and(|level| *level == 1.0));
+
+        // sags only while overloaded, the same way for the same seed
+        brown_out.overloaded = true;
+        let sagging = levels(&mut brown_out, 600);
+        assert!(sagging.iter().any(|level| *level < 0.6));
+        let mut again = behaviour("brown_out", Some(3));
+        again.overloaded = true;
+        let mut same = behaviour("brown_out", Some(3));
+        same.overloaded = true;
+        assert_eq!(levels(&mut again, 600), levels(&mut same, 600));
+    }
+
+    #[test]
+    fn inactive_pattern_sits_lit() {
+        let mut pulse = behaviour("pulse", None);
+        pulse.active = false;
+        assert_eq!(pulse.step(0.5), 1.0);
     }
 }
@@ -0,0 +1,102 @@
+use crate::settings::{AudioSettings,SettingsAsset};
+use bevy::prelude::*;
+use bevy::window::WindowFocused;
+use bevy_kira_audio::prelude::*;
+use std::collections::HashMap;
+
+pub struct MixerStatePlugin;
+
+// system state
+#[derive(Default)]
+pub struct MixerState {
+    // bus of every channel, and the volume the channel asked for before its bus
+    pub channel_buses: HashMap<String, String>,
+    pub channel_volumes: HashMap<String, f32>,
+    pub audio_settings: AudioSettings,
+    pub unfocused: bool,
+}
+
+impl MixerState {
+    // master, music, sfx, ambience or ui
+    pub fn route(
+        &mut self,
+        channel: &str,
+        bus: &str,
+    ) {
+        self.channel_buses.insert(channel.to_string(), bus.to_string());
+    }
+
+    pub fn set_channel_volume(
+        &mut self,
+        audio: &DynamicAudioChannels,
+        channel: &str,
+        volume: f32,
+    ) {
+        self.channel_volumes.insert(channel.to_string(), volume);
+        if audio.is_channel(channel) {
+            audio.channel(channel).set_volume((volume * self.gain(channel)) as f64);
+        }
+    }
+
+    fn gain(
+        &self,
+        channel: &str,
+    ) -> f32 {
+        let settings = &self.audio_settings;
+        if self.unfocused && settings.mute_on_focus_loss {
+            return 0.0;
+        }
+        let bus = match self.channel_buses.get(channel).map(|bus| bus.as_str()) {
+            Some("music") => settings.music,
+            Some("ambience") => settings.ambience,
+            Some("ui") => settings.ui,
+            Some("sfx") => settings.sfx,
+            Some("master") => 1.0,
+            bus => {
+                println!("unknown mixer bus :: {:?} {:?}", channel, bus);
+                1.0
+            }
+        };
+        settings.master * bus
+    }
+
+    fn apply(
+        &self,
+        audio: &DynamicAudioChannels,
+    ) {
+        for (channel, volume) in self.channel_volumes.iter() {
+            if audio.is_channel(channel) {
+                audio.channel(channel).set_volume((volume * self.gain(channel)) as f64);
+            }
+        }
+    }
+}
+
+impl Plugin for MixerStatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<MixerState>()
+        // settings and focus change in menus as well as in game
+        .add_system(update_mixer);
+    }
+}
+
+// follows bus volumes in settings, as loaded or changed by the player, and window focus
+fn update_mixer(
+    audio: Res<DynamicAudioChannels>,
+    settings: Res<SettingsAsset>,
+    mut mixer_state: ResMut<MixerState>,
+    mut focus_events: EventReader<WindowFocused>,
+) {
+    let mut changed = settings.is_changed();
+    if changed {
+        mixer_state.audio_settings = settings.audio_settings.clone();
+    }
+    for focus_event in focus_events.iter() {
+        mixer_state.unfocused = !focus_event.focused;
+        changed = true;
+    }
+    if changed {
+        mixer_state.apply(&audio);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::inputs::{CursorLockState};
+use crate::game_state::GameState;
+use crate::world::{WorldFlagsState,physics_step_dt};
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use std::collections::HashMap;
+
+pub struct MoversStatePlugin;
+
+pub enum MoversEventAction {
+    Start,
+    Stop,
+    Toggle,
+    // travel to a waypoint index and stop there
+    GoTo(usize),
+}
+
+pub struct MoversEvent {
+    pub action: MoversEventAction,
+    pub name: String,
+}
+
+// system state
+#[derive(Default)]
+pub struct MoversState {
+    pub movers: HashMap<String, WorldMoverState>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct WorldMoverState {
+    pub entity: Option<Entity>,
+    // world poses along the path
+    pub waypoints: Vec<Transform>,
+    // m/s and radians/s
+    pub speed: f32,
+    pub angular_speed: f32,
+    pub easing: String,
+    pub mode: String,
+    // flag that must be enabled for the mover to run
+    pub power: Option<String>,
+    pub running: bool,
+    // travelling along waypoint indices, resting when from equals to
+    pub from: usize,
+    pub to: usize,
+    pub forward: bool,
+    // eased over the leg, 0.0 to 1.0
+    pub progress: f32,
+    pub destination: Option<usize>,
+}
+
+impl WorldMoverState {
+    // waypoint following `at` in the travel direction, None at the end of a one-way run
+    fn next_waypoint(&mut self, at: usize) -> Option<usize> {
+        let count = self.waypoints.len();
+        if count < 2 {
+            return None;
+        }
+        if let Some(destination) = self.destination {
+            if destination == at {
+                return None;
+            }
+            self.forward = destination > at;
+        }
+        if self.forward {
+            if at + 1 < count {
+                return Some(at + 1);
+            }
+        } else if at > 0 {
+            return Some(at - 1);
+        }
+        match self.mode.as_str() {
+            "loop" => Some(if self.forward { 0 } else { count - 1 }),
+            "ping_pong" => {
+                self.forward = !self.forward;
+                Some(if self.forward { at + 1 } else { at - 1 })
+            }
+            _ => {
+                // once, the next run heads back
+                self.forward = !self.forward;
+                None
+            }
+        }
+    }
+
+    // pose partway along the current leg
+    fn pose(&self) -> Transform {
+        let (from, to) = (self.waypoints[self.from], self.waypoints[self.to]);
+        let t = ease(&self.easing, self.progress);
+        Transform {
+            translation: from.translation.lerp(to.translation, t),
+            rotation: from.rotation.slerp(to.rotation, t),
+            scale: Vec3::ONE,
+        }
+    }
+
+    // seconds to travel the current leg at speed
+    fn leg_duration(&self) -> f32 {
+        let (from, to) = (self.waypoints[self.from], self.waypoints[self.to]);
+        let linear = from.translation.distance(to.translation) / self.speed.max(f32::EPSILON);
+        let angular = from.rotation.angle_between(to.rotation) / self.angular_speed.max(f32::EPSILON);
+        linear.max(angular)
+    }
+}
+
+impl Plugin for MoversStatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<MoversState>()
+        .add_event::<MoversEvent>()
+        .add_system_set(
+            SystemSet::on_update(GameState::Running)
+            .with_system(update_movers_interaction)
+            .with_system(update_movers_movement.after(update_movers_interaction))
+        );
+    }
+}
+
+fn ease(
+    easing: &str,
+    t: f32,
+) -> f32 {
+    let t = t.clamp(0.0, 1.0);
+    match easing {
+        "ease_in" => t * t,
+        "ease_out" => t * (2.0 - t),
+        "ease_in_out" => t * t * (3.0 - 2.0 * t),
+        _ => t,
+    }
+}
+
+fn update_movers_interaction(
+    cursor_lock_state: Res<CursorLockState>,
+    mut movers_state: ResMut<MoversState>,
+    mut movers_events: EventReader<MoversEvent>,
+) {
+    if !cursor_lock_state.enabled {
+        return;
+    }
+
+    for movers_event in movers_events.iter() {
+        if let Some(mover_state) = movers_state.movers.get_mut(&movers_event.name) {
+            match movers_event.action {
+                MoversEventAction::Start => {
+                    mover_state.running = true;
+                }
+                MoversEventAction::Stop => {
+                    mover_state.running = false;
+                }
+                MoversEventAction::Toggle => {
+                    mover_state.running = !mover_state.running;
+                }
+                MoversEventAction::GoTo(waypoint) => {
+                    if waypoint < mover_state.waypoints.len() {
+                        mover_state.destination = Some(waypoint);
+                        mover_state.running = true;
+                    } else {
+                        println!("unknown mover waypoint :: {:?} {:?}", movers_event.name, waypoint);
+                    }
+                }
+            }
+        } else {
+            println!("unknown mover :: {:?}", movers_event.name);
+        }
+    }
+}
+
+// steps movers along their paths, kinematic velocity carries props, colliders and riders
+fn update_movers_movement(
+    time: Res<Time>,
+    cursor_lock_state: Res<CursorLockState>,
+    rapier_conf: Res<RapierConfiguration>,
+    world_flags_state: Res<WorldFlagsState>,
+    mut movers_state: ResMut<MoversState>,
+    mut movers: Query<(&Transform, &mut Velocity)>,
+) {
+    if !cursor_lock_state.enabled {
+        return;
+    }
+
+    let dt = physics_step_dt(&time, &rapier_conf);
+    for mover_state in movers_state.movers.values_mut() {
+        let (mover_transform, mut mover_velocity) = match mover_state.entity.and_then(|entity| movers.get_mut(entity).ok()) {
+            Some(mover) => mover,
+            None => continue,
+        };
+        let powered = mover_state.power.as_ref()
+            .is_none_or(|flag| world_flags_state.flags.get(flag).copied().unwrap_or(false));
+
+        if mover_state.running && powered && dt > f32::EPSILON {
+            // set off from rest towards the next waypoint
+            if mover_state.from == mover_state.to {
+                match mover_state.next_waypoint(mover_state.from) {
+                    Some(next) => {
+                        mover_state.to = next;
+                        mover_state.progress = 0.0;
+                    }
+                    None => {
+                        mover_state.running = false;
+                        mover_state.destination = None;
+                    }
+                }
+            }
+
+            if mover_state.from != mover_state.to {
+                mover_state.progress += dt / mover_state.leg_duration().max(f32::EPSILON);
+                if mover_state.progress >= 1.0 {
+                    // arrived, rest here or carry on next frame
+                    let target = mover_state.waypoints[mover_state.to];
+                    mover_state.from = mover_state.to;
+                    mover_state.progress = 0.0;
+                    if mover_state.destination == Some(mover_state.from) {
+                        mover_state.destination = None;
+                        mover_state.running = false;
+                    }
+                    set_mover_velocity(mover_transform, &mut mover_velocity, target, dt);
+                    continue;
+                }
+            }
+        }
+
+        if mover_state.running && powered && dt > f32::EPSILON {
+            set_mover_velocity(mover_transform, &mut mover_velocity, mover_state.pose(), dt);
+        } else if mover_velocity.linvel != Vec3::ZERO || mover_velocity.angvel != Vec3::ZERO {
+            *mover_velocity = Velocity::zero();
+        }
+    }
+}
+
+// velocity reaching a pose within one physics step
+fn set_mover_velocity(
+    mover_transform: &Transform,
+    mover_velocity: &mut Velocity,
+    target: Transform,
+    dt: f32,
+) {
+    let mut delta_rotation = target.rotation * mover_transform.rotation.inverse();
+    if delta_rotation.w < 0.0 {
+        delta_rotation = -delta_rotation;
+    }
+    mover_velocity.linvel = (target.translation - mover_transform.translation) / dt;
+    mover_velocity.angvel = delta_rotation.to_scaled_axis() / dt;
+}
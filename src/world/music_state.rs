@@ -0,0 +1,174 @@
+use crate::game_state::GameState;
+use crate::movement::Mover;
+use crate::world::{MixerState,WorldMusic,WorldState};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy_kira_audio::prelude::*;
+use std::marker::PhantomData;
+
+// music level under the pause menu, and the seconds it takes to get there
+const MUSIC_PAUSE_DUCK: f32 = 0.3;
+const MUSIC_DUCK_FADE: f32 = 0.5;
+
+pub struct MusicStatePlugin;
+
+// two channels per layer, the playing track fades out on one as the next fades in on the other
+#[derive(Default)]
+pub struct MusicLayerState {
+    pub active: usize,
+    pub tracks: [Option<String>; 2],
+    pub levels: [f32; 2],
+}
+
+// system state
+pub struct MusicState {
+    pub music: MusicLayerState,
+    pub ambience: MusicLayerState,
+    pub duck: f32,
+    // zone the player is in, if any
+    pub zone: Option<String>,
+}
+
+impl Default for MusicState {
+    fn default() -> Self {
+        Self {
+            music: MusicLayerState::default(),
+            ambience: MusicLayerState::default(),
+            duck: 1.0,
+            zone: None,
+        }
+    }
+}
+
+impl Plugin for MusicStatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<MusicState>()
+        // fades carry on through world loading and the pause menu
+        .add_system(update_music);
+    }
+}
+
+// tracks for where the player stands, the smallest zone around them wins over the world tracks
+fn music_targets(
+    music: &WorldMusic,
+    position: Option<Vec3>,
+) -> (Option<String>, Option<String>, Option<String>) {
+    let zone = position.and_then(|position| {
+        music.zones.iter()
+            .filter(|zone| ((position - zone.translation).abs() - zone.scale).max_element() <= 0.0)
+            .min_by(|a, b| (a.scale.x * a.scale.y * a.scale.z).total_cmp(&(b.scale.x * b.scale.y * b.scale.z)))
+    });
+    match zone {
+        Some(zone) => (
+            Some(zone.name.clone()),
+            zone.music.clone().or_else(|| music.music.clone()),
+            zone.ambience.clone().or_else(|| music.ambience.clone()),
+        ),
+        None => (None, music.music.clone(), music.ambience.clone()),
+    }
+}
+
+// channels and mixer the layers play through
+#[derive(SystemParam)]
+struct MusicChannels<'w, 's> {
+    asset_server: Res<'w, AssetServer>,
+    audio: ResMut<'w, DynamicAudioChannels>,
+    mixer_state: ResMut<'w, MixerState>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+// fade this frame, and the pause duck applied over it
+struct MusicFade {
+    step: f32,
+    duck: f32,
+    ducking: bool,
+}
+
+fn update_music(
+    time: Res<Time>,
+    game_state: Res<State<GameState>>,
+    world_state: Res<WorldState>,
+    mut music_channels: MusicChannels,
+    mut music_state: ResMut<MusicState>,
+    mover_query: Query<&GlobalTransform, With<Mover>>,
+) {
+    let dt = time.delta_seconds();
+    let paused = game_state.current() == &GameState::Paused;
+
+    let duck_target = if paused { MUSIC_PAUSE_DUCK } else { 1.0 };
+    let duck_step = (1.0 - MUSIC_PAUSE_DUCK) * dt / MUSIC_DUCK_FADE;
+    let duck = music_state.duck + (duck_target - music_state.duck).clamp(-duck_step, duck_step);
+    let ducking = duck != music_state.duck;
+    music_state.duck = duck;
+
+    // the pause menu keeps whatever is playing
+    let music_state = &mut *music_state;
+    let (music, ambience) = if paused {
+        (music_state.music.tracks[music_state.music.active].clone(),
+            music_state.ambience.tracks[music_state.ambience.active].clone())
+    } else {
+        let position = match game_state.current() {
+            GameState::Running => mover_query.get_single().ok().map(|transform| transform.translation()),
+            _ => None,
+        };
+        let (zone, music, ambience) = music_targets(&world_state.music, position);
+        music_state.zone = zone;
+        (music, ambience)
+    };
+
+    let step = dt / world_state.music.fade.max(f32::EPSILON);
+    music_channels.update_layer(&mut music_state.music, ["music_a", "music_b"], "music", music,
+        &MusicFade { step, duck, ducking });
+    music_channels.update_layer(&mut music_state.ambience, ["ambience_a", "ambience_b"], "ambience", ambience,
+        &MusicFade { step, duck: 1.0, ducking: false });
+}
+
+impl<'w, 's> MusicChannels<'w, 's> {
+    fn update_layer(
+        &mut self,
+        layer: &mut MusicLayerState,
+        channels: [&str; 2],
+        bus: &str,
+        track: Option<String>,
+        fade: &MusicFade,
+    ) {
+        let (audio, mixer_state) = (&mut *self.audio, &mut *self.mixer_state);
+        if layer.tracks[layer.active] != track {
+            let next = 1 - layer.active;
+            // a track still fading out on the other channel fades back in where it is
+            if layer.tracks[next] != track {
+                if !audio.is_channel(channels[next]) {
+                    audio.create_channel(channels[next]);
+                    mixer_state.route(channels[next], bus);
+                }
+                mixer_state.set_channel_volume(audio, channels[next], 0.0);
+                let channel = audio.channel(channels[next]);
+                channel.stop();
+                if let Some(track) = &track {
+                    channel.play(self.asset_server.load(&format!("audio/{}.ogg", track))).looped();
+                }
+                layer.levels[next] = 0.0;
+                layer.tracks[next] = track;
+            }
+            layer.active = next;
+        }
+
+        for (slot, channel) in channels.iter().enumerate() {
+            if layer.tracks[slot].is_none() {
+                continue;
+            }
+            let target = if slot == layer.active { 1.0 } else { 0.0 };
+            if layer.levels[slot] == target && !fade.ducking {
+                continue;
+            }
+            layer.levels[slot] += (target - layer.levels[slot]).clamp(-fade.step, fade.step);
+            mixer_state.set_channel_volume(audio, channel, layer.levels[slot] * fade.duck);
+            if slot != layer.active && layer.levels[slot] <= 0.0 {
+                audio.channel(channel).stop();
+                layer.tracks[slot] = None;
+            }
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use crate::game_state::GameState;
+use crate::movement::update_character_controllers;
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+// extra ray length below a rider's collider when looking for ground
+const PLATFORM_RIDER_GROUND_MARGIN: f32 = 0.1;
+
+// marks a body carried by kinematic platforms it stands on
+// dynamic bodies are carried through their velocity, character controllers move by delta
+#[derive(Clone,Component,Copy,Default)]
+pub struct PlatformRider {
+    // distance from the body origin to the bottom of its collider
+    pub ground_distance: f32,
+    pub platform: Option<Entity>,
+    // meters the platform carries the rider this step
+    pub delta: Vec3,
+    // platform velocity last added to a dynamic rider
+    pub carry_velocity: Vec3,
+}
+
+pub struct PlatformsStatePlugin;
+
+impl Plugin for PlatformsStatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .add_system_set(
+            SystemSet::on_update(GameState::Running)
+            .with_system(setup_platform_riders)
+            .with_system(update_platform_riders.after(setup_platform_riders).before(update_character_controllers))
+        );
+    }
+}
+
+// length of the step the physics pipeline is about to take
+pub fn physics_step_dt(
+    time: &Time,
+    rapier_conf: &RapierConfiguration,
+) -> f32 {
+    match rapier_conf.timestep_mode {
+        TimestepMode::Fixed { dt, .. } => dt,
+        TimestepMode::Variable { max_dt, time_scale, .. } => (time.delta_seconds() * time_scale).min(max_dt),
+        TimestepMode::Interpolated { dt, .. } => dt,
+    }
+}
+
+// tag every dynamic body as a potential rider
+fn setup_platform_riders(
+    mut commands: Commands,
+    bodies: Query<(Entity, &RigidBody, &Collider, Option<&Velocity>), Without<PlatformRider>>,
+) {
+    for (entity, rigid_body, collider, velocity) in bodies.iter() {
+        if matches!(rigid_body, RigidBody::Dynamic) {
+            let aabb = collider.raw.compute_local_aabb();
+            commands.entity(entity).insert(PlatformRider {
+                ground_distance: -aabb.mins.y,
+                ..Default::default()
+            });
+            if velocity.is_none() {
+                commands.entity(entity).insert(Velocity::default());
+            }
+        }
+    }
+}
+
+// move riders along with the kinematic body under them
+fn update_platform_riders(
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    rapier_conf: Res<RapierConfiguration>,
+    platforms: Query<(&RigidBody, &Velocity, &GlobalTransform), Without<PlatformRider>>,
+    mut riders: Query<(Entity, &RigidBody, &Transform, &mut PlatformRider, Option<&mut Velocity>)>,
+) {
+    if !rapier_conf.physics_pipeline_active {
+        return;
+    }
+
+    let dt = physics_step_dt(&time, &rapier_conf);
+
+    let ray_groups = InteractionGroups::new(0b0001, 0b0001);
+    for (rider_entity, rigid_body, rider_transform, mut rider, velocity) in riders.iter_mut() {
+        let ray_filter = QueryFilter { groups: Some(ray_groups), ..Default::default() }
+            .exclude_rigid_body(rider_entity);
+
+        // find the body under the rider, if it is a moving platform
+        rider.platform = rapier_context.cast_ray(
+            rider_transform.translation, -Vec3::Y,
+            rider.ground_distance + PLATFORM_RIDER_GROUND_MARGIN, true, ray_filter
+        )
+        .and_then(|(collider, _toi)| rapier_context.collider_parent(collider))
+        .filter(|body| matches!(platforms.get(*body),
+            Ok((RigidBody::KinematicVelocityBased, _, _))));
+
+        rider.delta = match rider.platform {
+            Some(platform) => {
+                let (_, platform_velocity, platform_transform) = platforms.get(platform).unwrap();
+                let offset = rider_transform.translation - platform_transform.translation();
+                let rotated_offset = Quat::from_scaled_axis(platform_velocity.angvel * dt) * offset;
+                platform_velocity.linvel * dt + rotated_offset - offset
+            }
+            None => Vec3::ZERO,
+        };
+
+        // dynamic bodies take on changes in platform velocity and keep it when leaving
+        if let (RigidBody::Dynamic, Some(mut velocity)) = (rigid_body, velocity) {
+            let carry_velocity = if rider.platform.is_some() && dt > 0.0 { rider.delta / dt } else { Vec3::ZERO };
+            if rider.platform.is_some() {
+                velocity.linvel += carry_velocity - rider.carry_velocity;
+            }
+            rider.carry_velocity = carry_velocity;
+        }
+    }
+}
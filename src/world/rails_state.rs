@@ -0,0 +1,538 @@
+use crate::inputs::{CursorLockState};
+use crate::game_state::GameState;
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+// upper bound of segments crossed by a train in one step
+const RAIL_MAX_SEGMENT_STEPS: usize = 8;
+const RAIL_SIGNAL_CLEAR_COLOR: Color = Color::rgb(0.1, 0.9, 0.2);
+const RAIL_SIGNAL_STOP_COLOR: Color = Color::rgb(0.9, 0.1, 0.1);
+
+pub struct RailsStatePlugin;
+
+pub enum RailsEventAction {
+    ThrowSwitch,
+}
+
+pub struct RailsEvent {
+    pub action: RailsEventAction,
+    pub name: String,
+}
+
+// system state
+#[derive(Default)]
+pub struct RailsState {
+    pub segments: HashMap<String, RailSegmentState>,
+    pub switches: HashMap<String, RailSwitchState>,
+    pub signals: HashMap<String, RailSignalState>,
+    pub stations: HashMap<String, RailStationState>,
+    // segment name to the trains on it, each with the span of segment distance it covers
+    pub occupancy: HashMap<String, HashMap<String, (f32, f32)>>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RailSegmentState {
+    pub points: Vec<Vec3>,
+    // distance along the segment at each point
+    pub distances: Vec<f32>,
+    pub next: Vec<String>,
+    pub prev: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RailSwitchState {
+    pub trunk: String,
+    pub branches: Vec<String>,
+    pub aligned: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RailSignalState {
+    pub segment: String,
+    pub forward: bool,
+    pub light_entity: Option<Entity>,
+    pub clear: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RailStationState {
+    pub segment: String,
+    pub distance: f32,
+}
+
+// position of a train on the rail network
+#[derive(Clone, Debug, Default)]
+pub struct RailPosition {
+    pub segment: String,
+    pub distance: f32,
+    // 1.0 when the train forward follows increasing distance, -1.0 otherwise
+    pub facing: f32,
+}
+
+impl RailSegmentState {
+    pub fn new(points: Vec<Vec3>, next: Vec<String>, prev: Vec<String>) -> Self {
+        let mut distances = Vec::with_capacity(points.len());
+        let mut total = 0.0;
+        for (idx, point) in points.iter().enumerate() {
+            if idx > 0 {
+                total += point.distance(points[idx - 1]);
+            }
+            distances.push(total);
+        }
+        Self { points, distances, next, prev }
+    }
+
+    pub fn length(&self) -> f32 {
+        self.distances.last().copied().unwrap_or(0.0)
+    }
+
+    // point and unit tangent at distance along the segment
+    pub fn point_at(&self, distance: f32) -> (Vec3, Vec3) {
+        if self.points.len() < 2 {
+            return (self.points.first().copied().unwrap_or(Vec3::ZERO), -Vec3::Z);
+        }
+        let distance = distance.clamp(0.0, self.length());
+        let idx = self.distances.iter().skip(1).position(|&d| d >= distance)
+            .unwrap_or(self.points.len() - 2);
+        let (start, end) = (self.points[idx], self.points[idx + 1]);
+        let span = self.distances[idx + 1] - self.distances[idx];
+        let t = if span > f32::EPSILON { (distance - self.distances[idx]) / span } else { 0.0 };
+        (start.lerp(end, t), (end - start).normalize_or_zero())
+    }
+
+    // distance along the segment closest to a point
+    pub fn project(&self, point: Vec3) -> f32 {
+        let mut best = (f32::MAX, 0.0);
+        for idx in 1..self.points.len() {
+            let (start, end) = (self.points[idx - 1], self.points[idx]);
+            let span = end - start;
+            let t = if span.length_squared() > f32::EPSILON {
+                ((point - start).dot(span) / span.length_squared()).clamp(0.0, 1.0)
+            } else { 0.0 };
+            let dist_sq = point.distance_squared(start + span * t);
+            if dist_sq < best.0 {
+                best = (dist_sq, self.distances[idx - 1] + t * span.length());
+            }
+        }
+        best.1
+    }
+}
+
+impl RailsState {
+    // segment reached by leaving a segment at one end, None when no aligned track
+    pub fn route_from(&self, segment: &str, forward: bool) -> Option<String> {
+        let segment_state = self.segments.get(segment)?;
+        let candidates = if forward { &segment_state.next } else { &segment_state.prev };
+
+        // facing points, the switch picks the branch
+        if let Some(switch) = self.switches.values().find(|switch| switch.trunk == segment
+                && switch.branches.iter().any(|branch| candidates.contains(branch))) {
+            return switch.branches.get(switch.aligned)
+                .filter(|branch| candidates.contains(branch))
+                .cloned();
+        }
+
+        // trailing points, the switch must be set for this branch
+        let route = candidates.first()?;
+        if let Some(switch) = self.switches.values().find(|switch| &switch.trunk == route
+                && switch.branches.iter().any(|branch| branch == segment)) {
+            if switch.branches.get(switch.aligned).map(|branch| branch.as_str()) != Some(segment) {
+                return None;
+            }
+        }
+        Some(route.clone())
+    }
+
+    // whether any train but this one holds a segment
+    pub fn occupied_by_other(&self, train: &str, segment: &str) -> bool {
+        self.occupancy.get(segment)
+            .is_some_and(|occupants| occupants.keys().any(|occupant| occupant != train))
+    }
+
+    // interlocking, a train may enter an aligned segment not held by another train
+    pub fn enter_route(&self, train: &str, segment: &str, forward: bool) -> Option<String> {
+        self.route_from(segment, forward).filter(|route| !self.occupied_by_other(train, route))
+    }
+
+    // marks the track from a rear end some length along the train forward as held by a train
+    pub fn occupy(&mut self, train: &str, rear: &RailPosition, length: f32) {
+        let mut position = rear.clone();
+        let mut remaining = length.max(0.0);
+        for _ in 0..RAIL_MAX_SEGMENT_STEPS {
+            let segment_length = self.segments.get(&position.segment).map_or(0.0, |s| s.length());
+            let end = (position.distance + remaining * position.facing).clamp(0.0, segment_length);
+            let span = self.occupancy.entry(position.segment.clone()).or_default()
+                .entry(train.to_string()).or_insert((f32::MAX, f32::MIN));
+            span.0 = span.0.min(position.distance.min(end));
+            span.1 = span.1.max(position.distance.max(end));
+
+            // carry on into the next segment when the train reaches past this one
+            remaining -= (end - position.distance).abs();
+            if remaining <= f32::EPSILON {
+                return;
+            }
+            match self.route_from(&position.segment, position.facing > 0.0) {
+                Some(route) => {
+                    position.distance = if position.facing > 0.0 {
+                        0.0
+                    } else {
+                        self.segments.get(&route).map_or(0.0, |s| s.length())
+                    };
+                    position.segment = route;
+                }
+                None => return,
+            }
+        }
+    }
+
+    // distance a train end may travel one way before reaching another train, up to max_distance
+    pub fn clearance_ahead(
+        &self,
+        train: &str,
+        from: &RailPosition,
+        forward: bool,
+        max_distance: f32,
+    ) -> f32 {
+        let mut segment = from.segment.clone();
+        let mut distance = from.distance;
+        let mut travelled = 0.0;
+        for _ in 0..=2 * self.segments.len() {
+            if travelled >= max_distance {
+                break;
+            }
+            // nearest edge of another train ahead in this segment, overlapping ones leave no room
+            let gap = self.occupancy.get(&segment).into_iter().flatten()
+                .filter(|(occupant, _)| occupant.as_str() != train)
+                .filter_map(|(_, &(start, end))| if forward {
+                    (end >= distance).then_some(start - distance)
+                } else {
+                    (start <= distance).then_some(distance - end)
+                })
+                .reduce(f32::min);
+            if let Some(gap) = gap {
+                return (travelled + gap.max(0.0)).min(max_distance);
+            }
+            let length = match self.segments.get(&segment) {
+                Some(segment_state) => segment_state.length(),
+                None => break,
+            };
+            travelled += if forward { length - distance } else { distance };
+            segment = match self.route_from(&segment, forward) {
+                Some(route) => route,
+                None => break,
+            };
+            distance = if forward { 0.0 } else { self.segments.get(&segment).map_or(0.0, |s| s.length()) };
+        }
+        max_distance
+    }
+
+    // moves a train along the network, returns the new position and whether it was stopped
+    pub fn advance(&self, train: &str, position: &RailPosition, travel: f32) -> (RailPosition, bool) {
+        self.walk(position, travel, |segment, forward| self.enter_route(train, segment, forward))
+    }
+
+    // position a distance away along aligned track, ignoring occupancy
+    pub fn offset(&self, position: &RailPosition, travel: f32) -> RailPosition {
+        self.walk(position, travel, |segment, forward| self.route_from(segment, forward)).0
+    }
+
+    fn walk(
+        &self,
+        position: &RailPosition,
+        travel: f32,
+        route_from: impl Fn(&str, bool) -> Option<String>,
+    ) -> (RailPosition, bool) {
+        let mut position = position.clone();
+        let mut remaining = travel * position.facing;
+        for _ in 0..RAIL_MAX_SEGMENT_STEPS {
+            let length = self.segments.get(&position.segment).map_or(0.0, |s| s.length());
+            let next_distance = position.distance + remaining;
+            if next_distance > length {
+                match route_from(&position.segment, true) {
+                    Some(route) => {
+                        remaining = next_distance - length;
+                        position.segment = route;
+                        position.distance = 0.0;
+                    }
+                    None => {
+                        position.distance = length;
+                        return (position, true);
+                    }
+                }
+            } else if next_distance < 0.0 {
+                match route_from(&position.segment, false) {
+                    Some(route) => {
+                        remaining = next_distance;
+                        position.distance = self.segments.get(&route).map_or(0.0, |s| s.length());
+                        position.segment = route;
+                    }
+                    None => {
+                        position.distance = 0.0;
+                        return (position, true);
+                    }
+                }
+            } else {
+                position.distance = next_distance;
+                return (position, false);
+            }
+        }
+        (position, true)
+    }
+
+    // distance travelling one way along segments to a point, None if not ahead within max_distance
+    pub fn distance_along(
+        &self,
+        from: &RailPosition,
+        forward: bool,
+        to_segment: &str,
+        to_distance: f32,
+        max_distance: f32,
+    ) -> Option<f32> {
+        let mut segment = from.segment.clone();
+        let mut distance = from.distance;
+        let mut travelled = 0.0;
+        for _ in 0..=2 * self.segments.len() {
+            if travelled > max_distance {
+                break;
+            }
+            let length = self.segments.get(&segment)?.length();
+            if segment == to_segment && (if forward { to_distance >= distance } else { to_distance <= distance }) {
+                return Some(travelled + (to_distance - distance).abs());
+            }
+            travelled += if forward { length - distance } else { distance };
+            segment = self.route_from(&segment, forward)?;
+            distance = if forward { 0.0 } else { self.segments.get(&segment)?.length() };
+        }
+        None
+    }
+
+    // distance a train may travel one way before a stop signal or refused route
+    pub fn authority_ahead(
+        &self,
+        train: &str,
+        from: &RailPosition,
+        forward: bool,
+        max_distance: f32,
+    ) -> f32 {
+        let mut segment = from.segment.clone();
+        let length = self.segments.get(&segment).map_or(0.0, |s| s.length());
+        let mut authority = if forward { length - from.distance } else { from.distance };
+        for _ in 0..=2 * self.segments.len() {
+            if authority >= max_distance {
+                break;
+            }
+            let signal_stop = self.signals.values().any(|signal| signal.segment == segment
+                && signal.forward == forward && !signal.clear);
+            if signal_stop {
+                return authority;
+            }
+            match self.enter_route(train, &segment, forward) {
+                Some(route) => {
+                    authority += self.segments.get(&route).map_or(0.0, |s| s.length());
+                    segment = route;
+                }
+                None => return authority,
+            }
+        }
+        max_distance
+    }
+
+    // point and unit tangent of a rail position
+    pub fn point_at(&self, position: &RailPosition) -> (Vec3, Vec3) {
+        self.segments.get(&position.segment)
+            .map_or((Vec3::ZERO, -Vec3::Z), |segment| segment.point_at(position.distance))
+    }
+
+    // aligns a switch to its next branch, false when unknown or locked under a train
+    pub fn throw_switch(&mut self, name: &str) -> bool {
+        let occupied = self.switches.get(name)
+            .is_some_and(|switch| self.occupancy.contains_key(&switch.trunk));
+        match self.switches.get_mut(name) {
+            // interlocking, points stay locked under a train
+            Some(_) if occupied => false,
+            Some(switch) if !switch.branches.is_empty() => {
+                switch.aligned = (switch.aligned + 1) % switch.branches.len();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // signals clear when the route past them is aligned and free
+    pub fn update_signals(&mut self) {
+        let aspects = self.signals.iter().map(|(signal_name, signal)| {
+            let clear = self.route_from(&signal.segment, signal.forward)
+                .is_some_and(|route| !self.occupancy.contains_key(&route));
+            (signal_name.clone(), clear)
+        }).collect::<Vec<(String, bool)>>();
+
+        for (signal_name, clear) in aspects {
+            if let Some(signal) = self.signals.get_mut(&signal_name) {
+                signal.clear = clear;
+            }
+        }
+    }
+}
+
+impl Plugin for RailsStatePlugin {
+    fn build(&self, app: &mut App) {
+        app
+        .init_resource::<RailsState>()
+        .add_event::<RailsEvent>()
+        .add_system_set(
+            SystemSet::on_update(GameState::Running)
+            .with_system(update_rails_interaction)
+            .with_system(update_rail_signals)
+        );
+    }
+}
+
+fn update_rails_interaction(
+    cursor_lock_state: Res<CursorLockState>,
+    mut rails_state: ResMut<RailsState>,
+    mut rails_events: EventReader<RailsEvent>,
+) {
+    if !cursor_lock_state.enabled {
+        return;
+    }
+
+    for rails_event in rails_events.iter() {
+        match rails_event.action {
+            RailsEventAction::ThrowSwitch => {
+                if !rails_state.throw_switch(&rails_event.name) {
+                    info!("Switch locked: {:?}", rails_event.name);
+                }
+            }
+        }
+    }
+}
+
+fn update_rail_signals(
+    mut rails_state: ResMut<RailsState>,
+    mut point_lights: Query<&mut PointLight>,
+) {
+    rails_state.update_signals();
+
+    for signal in rails_state.signals.values() {
+        if let Some(light_entity) = signal.light_entity {
+            if let Ok(mut point_light) = point_lights.get_mut(light_entity) {
+                let color = if signal.clear { RAIL_SIGNAL_CLEAR_COLOR } else { RAIL_SIGNAL_STOP_COLOR };
+                if point_light.color != color {
+                    point_light.color = color;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // trunk splitting at its far end into a main line and a siding, with a signal before the points
+    fn junction() -> RailsState {
+        let mut rails_state = RailsState::default();
+        rails_state.segments.insert("trunk".into(), RailSegmentState::new(
+            vec![Vec3::ZERO, Vec3::new(0.0, 0.0, 10.0)],
+            vec!["main".into(), "siding".into()], vec![]));
+        rails_state.segments.insert("main".into(), RailSegmentState::new(
+            vec![Vec3::new(0.0, 0.0, 10.0), Vec3::new(0.0, 0.0, 20.0)],
+            vec![], vec!["trunk".into()]));
+        rails_state.segments.insert("siding".into(), RailSegmentState::new(
+            vec![Vec3::new(0.0, 0.0, 10.0), Vec3::new(5.0, 0.0, 20.0)],
+            vec![], vec!["trunk".into()]));
+        rails_state.switches.insert("points".into(), RailSwitchState {
+            trunk: "trunk".into(),
+            branches: vec!["main".into(), "siding".into()],
+            aligned: 0,
+        });
+        rails_state.signals.insert("home".into(), RailSignalState {
+            segment: "trunk".into(),
+            forward: true,
+            ..Default::default()
+        });
+        rails_state
+    }
+
+    #[test]
+    fn switch_picks_aligned_branch() {
+        let mut rails_state = junction();
+        assert_eq!(rails_state.route_from("trunk", true), Some("main".to_string()));
+        assert!(rails_state.throw_switch("points"));
+        assert_eq!(rails_state.route_from("trunk", true), Some("siding".to_string()));
+        assert!(rails_state.throw_switch("points"));
+        assert_eq!(rails_state.route_from("trunk", true), Some("main".to_string()));
+    }
+
+    #[test]
+    fn trailing_points_refuse_unaligned_branch() {
+        let mut rails_state = junction();
+        assert_eq!(rails_state.route_from("main", false), Some("trunk".to_string()));
+        assert_eq!(rails_state.route_from("siding", false), None);
+        rails_state.throw_switch("points");
+        assert_eq!(rails_state.route_from("main", false), None);
+        assert_eq!(rails_state.route_from("siding", false), Some("trunk".to_string()));
+    }
+
+    #[test]
+    fn switch_locked_under_train() {
+        let mut rails_state = junction();
+        rails_state.occupy("train01", &RailPosition { segment: "trunk".into(), distance: 2.0, facing: 1.0 }, 4.0);
+        assert!(!rails_state.throw_switch("points"));
+        assert_eq!(rails_state.switches["points"].aligned, 0);
+        assert!(!rails_state.throw_switch("unknown"));
+    }
+
+    #[test]
+    fn train_follows_switch_across_junction() {
+        let mut rails_state = junction();
+        rails_state.throw_switch("points");
+        let position = RailPosition { segment: "trunk".into(), distance: 8.0, facing: 1.0 };
+        let (position, stopped) = rails_state.advance("train01", &position, 4.0);
+        assert!(!stopped);
+        assert_eq!(position.segment, "siding");
+        assert!((position.distance - 2.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn signal_stops_at_occupied_route() {
+        let mut rails_state = junction();
+        rails_state.update_signals();
+        assert!(rails_state.signals["home"].clear);
+
+        rails_state.occupy("train02", &RailPosition { segment: "main".into(), distance: 2.0, facing: 1.0 }, 4.0);
+        rails_state.update_signals();
+        assert!(!rails_state.signals["home"].clear);
+        let position = RailPosition { segment: "trunk".into(), distance: 4.0, facing: 1.0 };
+        assert_eq!(rails_state.authority_ahead("train01", &position, true, 50.0), 6.0);
+
+        // clear again once the points are set for the free siding
+        rails_state.throw_switch("points");
+        rails_state.update_signals();
+        assert!(rails_state.signals["home"].clear);
+    }
+
+    #[test]
+    fn trains_sharing_segment_are_held_apart() {
+        let mut rails_state = junction();
+        rails_state.occupy("train01", &RailPosition { segment: "main".into(), distance: 1.0, facing: 1.0 }, 2.0);
+        rails_state.occupy("train02", &RailPosition { segment: "main".into(), distance: 6.0, facing: 1.0 }, 3.0);
+        assert_eq!(rails_state.occupancy["main"].len(), 2);
+
+        // neither train owns the segment, a third one may not enter and both count against each other
+        assert_eq!(rails_state.enter_route("train03", "trunk", true), None);
+        assert_eq!(rails_state.enter_route("train01", "trunk", true), None);
+        assert!(rails_state.occupied_by_other("train02", "main"));
+
+        // each may close up to the other but not past it
+        let train01_front = RailPosition { segment: "main".into(), distance: 3.0, facing: 1.0 };
+        let train02_rear = RailPosition { segment: "main".into(), distance: 6.0, facing: 1.0 };
+        assert!((rails_state.clearance_ahead("train01", &train01_front, true, 50.0) - 3.0).abs() < 1e-4);
+        assert!((rails_state.clearance_ahead("train02", &train02_rear, false, 50.0) - 3.0).abs() < 1e-4);
+        assert_eq!(rails_state.clearance_ahead("train01", &train01_front, true, 1.0), 1.0);
+
+        // looking across the points from the trunk finds the nearer train
+        let approach = RailPosition { segment: "trunk".into(), distance: 4.0, facing: 1.0 };
+        assert!((rails_state.clearance_ahead("train03", &approach, true, 50.0) - 7.0).abs() < 1e-4);
+    }
+}
@@ -1,15 +1,37 @@
 use crate::game_state::GameState;
 use crate::inputs::{MouseCamera,MouseLookState};
-use crate::world::{WorldState,WorldSoundState};
+use crate::loading::AudioAssets;
+use crate::movement::{MovementState,Mover};
+use crate::world::{EchoesState,MixerState,WorldState,WorldSoundSpatial,WorldSoundState,train_sound_name};
 use bevy::prelude::*;
 use bevy_kira_audio::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+// m/s, for doppler shift
+const SPEED_OF_SOUND: f32 = 343.0;
+// seconds between occlusion raycasts, and occlusion change per second
+const SOUND_OCCLUSION_INTERVAL: f32 = 0.15;
+const SOUND_OCCLUSION_RATE: f32 = 4.0;
+const FOOTSTEPS_VOLUME: f32 = 0.2;
 
 pub struct SoundsStatePlugin;
 
+// system state
+#[derive(Default)]
+pub struct SoundsState {
+    pub occlusion_rmn: f32,
+}
+
 pub enum SoundsEventAction {
     Pause,
     Resume,
     Toggle,
+    SetVolume(f32),
+    SetPlaybackRate(f32),
+    // plays a sound file once on the named channel, heard from a position when set
+    PlayOnce(String, Option<Vec3>),
+    // carries the sound with a moving entity
+    Follow(Entity),
 }
 
 pub struct SoundsEvent {
@@ -20,14 +42,17 @@ pub struct SoundsEvent {
 impl Plugin for SoundsStatePlugin {
     fn build(&self, app: &mut App) {
         app
+        .init_resource::<SoundsState>()
         .add_event::<SoundsEvent>()
         .add_system_set(
             SystemSet::on_enter(GameState::Running)
             .with_system(setup_sounds_interaction)
         ).add_system_set(
             SystemSet::on_update(GameState::Running)
+            .with_system(update_train_sounds.before(update_sounds_interaction))
             .with_system(update_sounds_interaction)
-            .with_system(update_sounds_states)
+            .with_system(update_sounds_occlusion)
+            .with_system(update_sounds_states.after(update_sounds_interaction).after(update_sounds_occlusion))
         ).add_system_set(
             SystemSet::on_exit(GameState::Running)
             .with_system(exit_sounds_interaction)
@@ -38,6 +63,7 @@ impl Plugin for SoundsStatePlugin {
 fn setup_sounds_interaction(
     asset_server: Res<AssetServer>,
     mut audio: ResMut<DynamicAudioChannels>,
+    mut mixer_state: ResMut<MixerState>,
     mut world_state: ResMut<WorldState>,
 ) {
     for (sound_name, sounds_state) in world_state.animatable_sounds.iter() {
@@ -60,85 +86,147 @@ fn setup_sounds_interaction(
         }
     }
 
-    // footsteps
+    // footsteps, played a step at a time
+    if !audio.is_channel("footsteps") {
+        audio.create_channel("footsteps");
+    }
+    mixer_state.route("footsteps", "sfx");
+    mixer_state.set_channel_volume(&audio, "footsteps", FOOTSTEPS_VOLUME);
+    // trains
+    let train_names = world_state.animatable_trains.keys().cloned().collect::<Vec<String>>();
+    for train_name in train_names.iter() {
+        setup_train_sound(&asset_server, &mut audio, &mut mixer_state, &mut world_state, train_name);
+    }
 
-    if audio.is_channel("footsteps") {
-        let channel = audio.channel("footsteps");
-        if let Some(sound_state) = world_state.animatable_sounds.get("footsteps") {
-            if sound_state.paused {
-                channel.pause();
-            } else {
-                channel.resume();
-            }
-        } else {
-            channel.pause();
-            world_state.animatable_sounds.insert("footsteps".into(), WorldSoundState {
-                sound: "steps_snow_dry".into(),
-                position: Vec3::ZERO,
-                paused: true,
-                volume: 0.2,
-                panning: 0.5,
-            });
+    // every channel plays through its mixer bus
+    for (sound_name, sounds_state) in world_state.animatable_sounds.iter() {
+        mixer_state.route(sound_name, &sounds_state.bus);
+        if sounds_state.spatial.is_none() {
+            mixer_state.set_channel_volume(&audio, sound_name, sounds_state.volume);
         }
-    } else {
-        let channel = audio
-            .create_channel("footsteps");
-        channel
-            .play(asset_server.load(&format!("audio/steps_snow_dry.ogg")))
-            .with_volume(0.2)
+    }
+}
+
+// rolling sound of a train on its own channel, paused unless the train already runs
+fn setup_train_sound(
+    asset_server: &AssetServer,
+    audio: &mut DynamicAudioChannels,
+    mixer_state: &mut MixerState,
+    world_state: &mut WorldState,
+    train_name: &str,
+) {
+    let train_state = match world_state.animatable_trains.get(train_name) {
+        Some(train_state) => train_state,
+        None => return,
+    };
+    let sound_name = train_sound_name(train_name);
+    let sound_state = world_state.animatable_sounds.entry(sound_name.clone()).or_insert(WorldSoundState {
+        sound: "train_rolling".into(),
+        position: Vec3::ZERO,
+        paused: !train_state.running,
+        volume: 0.5,
+        playback_rate: 1.0,
+        panning: 0.5,
+        bus: "sfx".into(),
+        spatial: Some(train_sound_spatial()),
+        emitter: train_state.cars.first().and_then(|car| car.entity),
+        ..default()
+    });
+    if !audio.is_channel(&sound_name) {
+        audio
+            .create_channel(&sound_name)
+            .play(asset_server.load(&format!("audio/{}.ogg", sound_state.sound)))
             .looped();
+    }
+    let channel = audio.channel(&sound_name);
+    if sound_state.paused {
         channel.pause();
-        world_state.animatable_sounds.insert("footsteps".into(), WorldSoundState {
-            sound: "steps_snow_dry".into(),
-            position: Vec3::ZERO,
-            paused: true,
-            volume: 0.2,
-            panning: 0.5,
-        });
-    }
-    // train
-    if audio.is_channel("train") {
-        let channel = audio.channel("train");
-        if let Some(sound_state) = world_state.animatable_sounds.get("train") {
-            if sound_state.paused {
-                channel.pause();
-            } else {
-                channel.resume();
-            }
-        } else {
-            channel.pause();
-            world_state.animatable_sounds.insert("train".into(), WorldSoundState {
-                sound: "train_rolling".into(),
-                position: Vec3::ZERO,
-                paused: true,
-                volume: 0.5,
-                panning: 0.5,
-            });
-        }
     } else {
-        let channel = audio
-            .create_channel("train");
-        channel
-            .play(asset_server.load(&format!("audio/train_rolling.ogg")))
-            .with_volume(1.0)
-            .looped();
-        channel.pause();
-        world_state.animatable_sounds.insert("train".into(), WorldSoundState {
-            sound: "train_rolling".into(),
-            position: Vec3::ZERO,
-            paused: true,
-            volume: 0.5,
-            panning: 0.5,
-        });
+        channel.resume();
+    }
+    mixer_state.route(&sound_name, &sound_state.bus);
+}
+
+// trains split off while running get a rolling sound of their own
+fn update_train_sounds(
+    asset_server: Res<AssetServer>,
+    mut audio: ResMut<DynamicAudioChannels>,
+    mut mixer_state: ResMut<MixerState>,
+    mut world_state: ResMut<WorldState>,
+) {
+    let train_names = world_state.animatable_trains.keys()
+        .filter(|train_name| !world_state.animatable_sounds.contains_key(&train_sound_name(train_name)))
+        .cloned()
+        .collect::<Vec<String>>();
+    for train_name in train_names.iter() {
+        setup_train_sound(&asset_server, &mut audio, &mut mixer_state, &mut world_state, train_name);
+    }
+}
+
+// heard along the line, pitching past the listener
+fn train_sound_spatial() -> WorldSoundSpatial {
+    WorldSoundSpatial {
+        range: 80.0,
+        min_distance: 4.0,
+        rolloff: "inverse".into(),
+        min_volume: 0.0,
+        max_volume: 1.0,
+        doppler: true,
+        ..default()
+    }
+}
+
+// one-shot effects, panned and attenuated once as they start
+fn effect_sound_spatial() -> WorldSoundSpatial {
+    WorldSoundSpatial {
+        range: 20.0,
+        min_distance: 2.0,
+        rolloff: "inverse".into(),
+        ..default()
     }
 }
 
 fn update_sounds_interaction(
-    audio: Res<DynamicAudioChannels>,
+    asset_server: Res<AssetServer>,
+    audio_assets: Res<AudioAssets>,
+    mut audio: ResMut<DynamicAudioChannels>,
+    movement_state: Res<MovementState>,
+    mouse_look: Res<MouseLookState>,
+    mut mixer_state: ResMut<MixerState>,
+    mut echoes_state: ResMut<EchoesState>,
     mut world_state: ResMut<WorldState>,
     mut sounds_events: EventReader<SoundsEvent>,
+    camera_query: Query<&GlobalTransform, With<MouseCamera>>,
+    mover_query: Query<(&GlobalTransform, &Mover)>,
 ) {
     for sounds_event in sounds_events.iter() {
+        // one-shot channels are created on demand
+        if let SoundsEventAction::PlayOnce(sound, position) = &sounds_event.action {
+            if !audio.is_channel(&sounds_event.name) {
+                audio.create_channel(&sounds_event.name);
+                mixer_state.route(&sounds_event.name, "sfx");
+                mixer_state.set_channel_volume(&audio, &sounds_event.name, 1.0);
+            }
+            let channel = audio.channel(&sounds_event.name);
+            let source = audio_assets.sound(sound).unwrap_or_else(|| asset_server.load(&format!("audio/{}.ogg", sound)));
+            let mut play = channel.play(source);
+            let (mut volume, mut panning) = (1.0, 0.5);
+            if let Some(position) = position {
+                let (mover_transform, mover) = mover_query.single();
+                let listener = listener_position(&movement_state, camera_query.single(), mover_transform, mover);
+                let to_sound = *position - listener;
+                volume = spatial_gain(&effect_sound_spatial(), to_sound.length());
+                panning = 0.5 + 0.5 * mouse_look.right.dot(to_sound.normalize_or_zero());
+                play
+                    .with_volume(volume as f64)
+                    .with_panning(panning as f64);
+            }
+            // the space around the listener answers back
+            let channel_volume = mixer_state.channel_volumes.get(&sounds_event.name).copied().unwrap_or(1.0);
+            echoes_state.reflect(sound, volume * channel_volume, panning);
+            continue;
+        }
+
         if let Some(sounds_state) = world_state.animatable_sounds.get_mut(&sounds_event.name) {
             match sounds_event.action {
                 SoundsEventAction::Toggle => {
@@ -158,39 +246,177 @@ fn update_sounds_interaction(
                     audio.channel(&sounds_event.name).resume();
                     sounds_state.paused = false;
                 }
+                // positional sounds pass volume and rate through the spatializer
+                SoundsEventAction::SetVolume(volume) => {
+                    if (sounds_state.volume - volume).abs() > f32::EPSILON {
+                        if sounds_state.spatial.is_none() {
+                            mixer_state.set_channel_volume(&audio, &sounds_event.name, volume);
+                        }
+                        sounds_state.volume = volume;
+                    }
+                }
+                SoundsEventAction::SetPlaybackRate(playback_rate) => {
+                    if (sounds_state.playback_rate - playback_rate).abs() > f32::EPSILON {
+                        if sounds_state.spatial.is_none() {
+                            audio.channel(&sounds_event.name).set_playback_rate(playback_rate as f64);
+                        }
+                        sounds_state.playback_rate = playback_rate;
+                    }
+                }
+                SoundsEventAction::Follow(entity) => {
+                    sounds_state.emitter = Some(entity);
+                    sounds_state.distance = None;
+                }
+                SoundsEventAction::PlayOnce(..) => {}
             }
         }
     }
 }
 
+// volume share heard at distance
+fn spatial_gain(
+    spatial: &WorldSoundSpatial,
+    distance: f32,
+) -> f32 {
+    if distance > spatial.range {
+        return 0.0;
+    }
+    let reference = spatial.min_distance.max(0.0);
+    let distance = distance.max(reference);
+    match spatial.rolloff.as_str() {
+        "inverse" => {
+            let reference = reference.max(0.01);
+            reference / (reference + spatial.rolloff_factor * (distance - reference))
+        }
+        "exponential" => {
+            let reference = reference.max(0.01);
+            (distance / reference).powf(-spatial.rolloff_factor)
+        }
+        _ => 1.0 - spatial.rolloff_factor * (distance - reference) / (spatial.range - reference).max(f32::EPSILON),
+    }.clamp(0.0, 1.0)
+}
+
+// the player hears in third person, the camera otherwise
+pub fn listener_position(
+    movement_state: &MovementState,
+    camera_transform: &GlobalTransform,
+    mover_transform: &GlobalTransform,
+    mover: &Mover,
+) -> Vec3 {
+    if mover.third_person && !movement_state.noclip {
+        mover_transform.translation() + 0.8 * Vec3::Y
+    } else {
+        camera_transform.translation()
+    }
+}
+
+// casts from the listener to each emitter against world colliders, a few times a second
+fn update_sounds_occlusion(
+    time: Res<Time>,
+    rapier_context: Res<RapierContext>,
+    movement_state: Res<MovementState>,
+    mut sounds_state: ResMut<SoundsState>,
+    mut world_state: ResMut<WorldState>,
+    camera_query: Query<&GlobalTransform, With<MouseCamera>>,
+    mover_query: Query<(Entity, &GlobalTransform, &Mover)>,
+) {
+    sounds_state.occlusion_rmn -= time.delta_seconds();
+    if sounds_state.occlusion_rmn > 0.0 {
+        return;
+    }
+    sounds_state.occlusion_rmn = SOUND_OCCLUSION_INTERVAL;
+
+    let (mover_entity, mover_transform, mover) = mover_query.single();
+    let listener = listener_position(&movement_state, camera_query.single(), mover_transform, mover);
+    let ray_groups = InteractionGroups::new(0b0001, 0b0001);
+
+    for world_sound_state in world_state.animatable_sounds.values_mut() {
+        let spatial = match &world_sound_state.spatial {
+            Some(spatial) => spatial,
+            None => continue,
+        };
+        let to_sound = world_sound_state.position - listener;
+        let distance = to_sound.length();
+        if world_sound_state.paused || distance > spatial.range || distance < f32::EPSILON {
+            world_sound_state.occluded = false;
+            continue;
+        }
+        // neither the player nor the body carrying the sound blocks it
+        let mut ray_filter = QueryFilter { groups: Some(ray_groups), ..Default::default() }
+            .exclude_rigid_body(mover_entity);
+        if let Some(emitter) = world_sound_state.emitter {
+            ray_filter = ray_filter.exclude_rigid_body(emitter);
+        }
+        world_sound_state.occluded = rapier_context.cast_ray(
+            listener, to_sound / distance, (distance - 0.1).max(0.0), true, ray_filter
+        ).is_some();
+    }
+}
+
+// attenuates, pans and doppler shifts positional sounds around the listener
 fn update_sounds_states(
+    time: Res<Time>,
     audio: Res<DynamicAudioChannels>,
-    mut world_state: ResMut<WorldState>,
-    query: Query<&GlobalTransform, With<MouseCamera>>,
+    mut mixer_state: ResMut<MixerState>,
+    movement_state: Res<MovementState>,
     mouse_look: Res<MouseLookState>,
+    mut world_state: ResMut<WorldState>,
+    camera_query: Query<&GlobalTransform, With<MouseCamera>>,
+    mover_query: Query<(&GlobalTransform, &Mover)>,
+    emitters: Query<&GlobalTransform>,
 ) {
-    let camera_transform = query.single();
+    let (mover_transform, mover) = mover_query.single();
+    let listener = listener_position(&movement_state, camera_query.single(), mover_transform, mover);
+    let dt = time.delta_seconds();
 
-    // for each playing audio, update state and panning from (camera_pos - audio_pos)
-    for (sound_name, mut sounds_state) in world_state.animatable_sounds.iter_mut() {
-        if !sounds_state.paused {
-            if sound_name == "train" || sound_name == "footsteps" {
-                continue;
-            }
+    for (sound_name, sounds_state) in world_state.animatable_sounds.iter_mut() {
+        if sounds_state.paused {
+            continue;
+        }
+        let spatial = match &sounds_state.spatial {
+            Some(spatial) => spatial,
+            None => continue,
+        };
+        if let Some(emitter_transform) = sounds_state.emitter.and_then(|entity| emitters.get(entity).ok()) {
+            sounds_state.position = emitter_transform.translation();
+        }
 
-            let diff_v = camera_transform.translation() - sounds_state.position;
-            let panning = (mouse_look.right.dot(diff_v) * 0.5)
-                .max(-1.0).min(1.0) * -0.5 + 0.5;
-            let volume = 1.0 - 0.1 * diff_v.length().max(0.0).min(10.0);
-            if (sounds_state.panning - panning).abs() > f32::EPSILON {
-                audio.channel(sound_name).set_panning(panning as f64);
-                sounds_state.panning = panning;
-            }
-            if (sounds_state.volume - volume).abs() > f32::EPSILON {
-                audio.channel(sound_name).set_volume(volume as f64);
-                sounds_state.volume = volume;
+        let to_sound = sounds_state.position - listener;
+        let distance = to_sound.length();
+        // centred when close, panned by bearing further out
+        let bearing = mouse_look.right.dot(to_sound.normalize_or_zero());
+        let mut panning = 0.5 + 0.5 * bearing * (distance / spatial.min_distance.max(1.0)).min(1.0);
+        let mut volume = sounds_state.volume * spatial_gain(spatial, distance).clamp(spatial.min_volume, spatial.max_volume);
+
+        // no filters on kira channels, walls muffle by dropping volume and stereo width
+        let step = SOUND_OCCLUSION_RATE * dt;
+        let occlusion_target = if sounds_state.occluded { 1.0 } else { 0.0 };
+        sounds_state.occlusion += (occlusion_target - sounds_state.occlusion).clamp(-step, step);
+        volume *= 1.0 - sounds_state.occlusion * (1.0 - spatial.occlusion.clamp(0.0, 1.0));
+        panning = 0.5 + (panning - 0.5) * (1.0 - 0.5 * sounds_state.occlusion);
+
+        // receding emitters drop in pitch, approaching ones rise
+        let mut playback_rate = sounds_state.playback_rate;
+        if spatial.doppler && dt > f32::EPSILON {
+            if let Some(last_distance) = sounds_state.distance {
+                let receding = spatial.doppler_factor * (distance - last_distance) / dt;
+                playback_rate *= (SPEED_OF_SOUND / (SPEED_OF_SOUND + receding).max(f32::EPSILON)).clamp(0.5, 2.0);
             }
         }
+        sounds_state.distance = Some(distance);
+
+        if (sounds_state.panning - panning).abs() > f32::EPSILON {
+            audio.channel(sound_name).set_panning(panning as f64);
+            sounds_state.panning = panning;
+        }
+        if (sounds_state.output_volume - volume).abs() > f32::EPSILON {
+            mixer_state.set_channel_volume(&audio, sound_name, volume);
+            sounds_state.output_volume = volume;
+        }
+        if (sounds_state.output_rate - playback_rate).abs() > f32::EPSILON {
+            audio.channel(sound_name).set_playback_rate(playback_rate as f64);
+            sounds_state.output_rate = playback_rate;
+        }
     }
 }
 
@@ -1,13 +1,41 @@
-use crate::inputs::{CursorLockState};
+use crate::inputs::{CursorLockState,KeyInputMap,KeyInputState};
 use crate::game_state::GameState;
-use crate::movement::{Mover};
-use crate::world::{SoundsEvent,SoundsEventAction,WorldState};
+use crate::loading::{FontAssets};
+use crate::world::{DoorEvent,DoorEventAction,RailsState,SoundsEvent,
+    SoundsEventAction,TrainCarState,WorldState,WorldTrainState,physics_step_dt};
 use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+const TRAIN_STOPPED_SPEED: f32 = 0.05;
+const TRAIN_SOUND_MIN_VOLUME: f32 = 0.3;
+const TRAIN_SOUND_MIN_RATE: f32 = 0.6;
+const AUTOPILOT_STOP_TOLERANCE: f32 = 0.3;
+const AUTOPILOT_APPROACH_DISTANCE: f32 = 10.0;
+const AUTOPILOT_LOOKAHEAD: f32 = 200.0;
+const AUTOPILOT_CRUISE_RATIO: f32 = 0.6;
+// slack between coupler ends of neighbouring cars
+const TRAIN_COUPLER_GAP: f32 = 0.3;
+const TRAIN_COUPLING_DISTANCE: f32 = 1.0;
+const TRAIN_COUPLING_SPEED: f32 = 0.5;
+
+// system state
+#[derive(Default)]
+pub struct TrainsState {
+    pub hud_entity: Option<Entity>,
+}
+
+// Tag for UI component
+#[derive(Component)]
+struct TrainsHudText;
 
 pub struct TrainsStatePlugin;
 
 pub enum TrainsEventAction {
     StartControl,
+    StopControl,
+    // couple or uncouple at the car holding the interactable entity
+    Couple(Entity),
+    Uncouple(Entity),
 }
 
 pub struct TrainsEvent {
@@ -18,79 +46,684 @@ pub struct TrainsEvent {
 impl Plugin for TrainsStatePlugin {
     fn build(&self, app: &mut App) {
         app
+        .insert_resource(TrainsState::default())
         .add_event::<TrainsEvent>()
+        .add_system_set(SystemSet::on_enter(GameState::Running)
+            .with_system(setup_trains_hud))
         .add_system_set(
             SystemSet::on_update(GameState::Running)
             .with_system(update_trains_interaction)
+            .with_system(update_trains_autopilot.before(update_trains_movement))
             .with_system(update_trains_movement)
-        );
+            .with_system(update_trains_hud)
+        )
+        .add_system_set(SystemSet::on_exit(GameState::Running)
+            .with_system(exit_trains_hud));
     }
 }
 
 
+fn setup_trains_hud(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    mut trains_state: ResMut<TrainsState>,
+    world_state: Res<WorldState>,
+) {
+    trains_state.hud_entity = Some(commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: UiRect {
+                    right: Val::Percent(2.),
+                    bottom: Val::Percent(4.),
+                    ..default()
+                },
+                ..default()
+            },
+            color: Color::NONE.into(),
+            visibility: Visibility { is_visible: world_state.active_train.is_some() },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text {
+                    sections: vec![TextSection {
+                        value: "".to_string(),
+                        style: TextStyle {
+                            font: font_assets.fira_sans.clone(),
+                            font_size: 20.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                    }],
+                    alignment: TextAlignment::TOP_LEFT,
+                },
+                ..Default::default()
+            })
+            .insert(TrainsHudText)
+            ;
+        })
+        .id());
+}
+
 fn update_trains_interaction(
     cursor_lock_state: Res<CursorLockState>,
+    mut rails_state: ResMut<RailsState>,
     mut world_state: ResMut<WorldState>,
     mut train_events: EventReader<TrainsEvent>,
+    mut sounds_events: EventWriter<SoundsEvent>,
+    parents: Query<&Parent>,
 ) {
     if !cursor_lock_state.enabled {
         return;
     }
 
     for train_event in train_events.iter() {
-        if world_state.animatable_trains.contains_key(&train_event.train) {
-            if matches!(train_event.action, TrainsEventAction::StartControl) {
-                world_state.active_train = Some(train_event.train.clone());
+        match train_event.action {
+            TrainsEventAction::StartControl => {
+                if world_state.animatable_trains.contains_key(&train_event.train) {
+                    world_state.active_train = Some(train_event.train.clone());
+                }
+            }
+            TrainsEventAction::StopControl => {
+                if world_state.active_train.as_ref() == Some(&train_event.train) {
+                    stop_train_control(&mut world_state);
+                }
+            }
+            // coupler interactables sit directly on their car
+            TrainsEventAction::Couple(entity) => {
+                if let Ok(car) = parents.get(entity) {
+                    // the joined train rolls on under our name, silence its own sound
+                    if let Some(other_name) = couple_train_car(&rails_state, &mut world_state, car.get()) {
+                        sounds_events.send(SoundsEvent {
+                            action: SoundsEventAction::Pause,
+                            name: train_sound_name(&other_name),
+                        });
+                    }
+                    update_train_occupancy(&mut rails_state, &world_state);
+                }
+            }
+            // each half holds only the track under its own cars from the start
+            TrainsEventAction::Uncouple(entity) => {
+                if let Ok(car) = parents.get(entity) {
+                    uncouple_train_car(&mut world_state, car.get());
+                    update_train_occupancy(&mut rails_state, &world_state);
+                }
+            }
+        }
+    }
+}
+
+// releases the active train, leaving it to coast on idle throttle
+fn stop_train_control(
+    world_state: &mut WorldState,
+) {
+    if let Some(train_name) = world_state.active_train.take() {
+        if let Some(train_state) = world_state.animatable_trains.get_mut(&train_name) {
+            train_state.throttle = 0;
+            train_state.braking = false;
+        }
+    }
+}
+
+// channel of the rolling sound of a train
+pub fn train_sound_name(
+    train_name: &str,
+) -> String {
+    format!("train_{}", train_name)
+}
+
+// distance between the origins of two coupled cars, ahead first
+pub fn train_car_spacing(
+    ahead: &TrainCarState,
+    behind: &TrainCarState,
+) -> f32 {
+    ahead.rear + TRAIN_COUPLER_GAP + behind.front
+}
+
+// lines cars up along the rails at coupler spacing from an anchor car
+pub fn place_train_cars(
+    rails_state: &RailsState,
+    cars: &mut [TrainCarState],
+    anchor: usize,
+) {
+    for idx in anchor + 1..cars.len() {
+        let spacing = train_car_spacing(&cars[idx - 1], &cars[idx]);
+        cars[idx].rail = cars[idx - 1].rail.as_ref().map(|rail| rails_state.offset(rail, -spacing));
+    }
+    for idx in (0..anchor).rev() {
+        let spacing = train_car_spacing(&cars[idx], &cars[idx + 1]);
+        cars[idx].rail = cars[idx + 1].rail.as_ref().map(|rail| rails_state.offset(rail, spacing));
+    }
+}
+
+// world points of the front and rear couplers of a train on rails
+fn train_coupler_ends(
+    rails_state: &RailsState,
+    train_state: &WorldTrainState,
+) -> Option<(Vec3, Vec3)> {
+    let (first, last) = (train_state.cars.first()?, train_state.cars.last()?);
+    let front = rails_state.offset(first.rail.as_ref()?, first.front);
+    let rear = rails_state.offset(last.rail.as_ref()?, -last.rear);
+    Some((rails_state.point_at(&front).0, rails_state.point_at(&rear).0))
+}
+
+// joins the train holding a car with another train stopped against either end, returns the joined train
+fn couple_train_car(
+    rails_state: &RailsState,
+    world_state: &mut WorldState,
+    car_entity: Entity,
+) -> Option<String> {
+    let (train_name, _) = find_train_car(world_state, car_entity)?;
+    let train_state = &world_state.animatable_trains[&train_name];
+    let (front, rear) = match train_coupler_ends(rails_state, train_state) {
+        Some(ends) if train_state.speed.abs() < TRAIN_COUPLING_SPEED => ends,
+        _ => return None,
+    };
+
+    // find the other train end within reach, as (our rear, their front)
+    let coupling = world_state.animatable_trains.iter()
+        .filter(|(other_name, other_state)| **other_name != train_name
+            && other_state.speed.abs() < TRAIN_COUPLING_SPEED)
+        .find_map(|(other_name, other_state)| {
+            let (other_front, other_rear) = train_coupler_ends(rails_state, other_state)?;
+            [(false, false, front, other_rear), (false, true, front, other_front),
+                (true, true, rear, other_front), (true, false, rear, other_rear)]
+                .into_iter()
+                .find(|(_, _, end, other_end)| end.distance(*other_end) <= TRAIN_COUPLING_DISTANCE)
+                .map(|(at_rear, at_front, _, _)| (other_name.clone(), at_rear, at_front))
+        });
+    let (other_name, at_rear, at_front) = match coupling {
+        Some(coupling) => coupling,
+        None => {
+            info!("Nothing to couple: {:?}", train_name);
+            return None;
+        }
+    };
+
+    // bring the other cars into our order and orientation
+    let other_state = world_state.animatable_trains.remove(&other_name).unwrap();
+    let forward = |train_state: &WorldTrainState| train_state.cars[0].rail.as_ref()
+        .map_or(Vec3::ZERO, |rail| rails_state.point_at(rail).1 * rail.facing);
+    let train_state = world_state.animatable_trains.get_mut(&train_name).unwrap();
+    let reversed = forward(train_state).dot(forward(&other_state)) < 0.0;
+    let mut other_cars = other_state.cars;
+    if at_rear != at_front {
+        other_cars.reverse();
+    }
+    if reversed {
+        for car in other_cars.iter_mut() {
+            car.flipped = !car.flipped;
+            std::mem::swap(&mut car.front, &mut car.rear);
+            if let Some(rail) = car.rail.as_mut() {
+                rail.facing = -rail.facing;
             }
         }
     }
+
+    let anchor = if at_rear {
+        train_state.cars.extend(other_cars);
+        0
+    } else {
+        let anchor = other_cars.len();
+        train_state.cars.splice(0..0, other_cars);
+        anchor
+    };
+    place_train_cars(rails_state, &mut train_state.cars, anchor);
+    train_state.parent_entity = train_state.cars[0].entity;
+    train_state.speed = 0.0;
+
+    if world_state.active_train.as_ref() == Some(&other_name) {
+        world_state.active_train = Some(train_name);
+    }
+    Some(other_name)
+}
+
+// splits a train behind a car, or ahead of it at the tail, the split cars run as a new train
+fn uncouple_train_car(
+    world_state: &mut WorldState,
+    car_entity: Entity,
+) {
+    let (train_name, car_idx) = match find_train_car(world_state, car_entity) {
+        Some(found) => found,
+        None => return,
+    };
+    let train_state = world_state.animatable_trains.get_mut(&train_name).unwrap();
+    if train_state.cars.len() < 2 {
+        return;
+    }
+    let split = (car_idx + 1).min(train_state.cars.len() - 1);
+    let cars = train_state.cars.split_off(split);
+    let split_state = WorldTrainState {
+        parent_entity: cars[0].entity,
+        running: false,
+        dynamics: train_state.dynamics.clone(),
+        throttle: 0,
+        direction: train_state.direction,
+        braking: false,
+        speed: train_state.speed,
+        cars,
+        autopilot: None,
+    };
+
+    let split_name = (1..).map(|idx| format!("{}.{}", train_name, idx))
+        .find(|name| !world_state.animatable_trains.contains_key(name))
+        .unwrap();
+    world_state.animatable_trains.insert(split_name, split_state);
+}
+
+// car at the end of the train moving in a direction, 1.0 forward or -1.0 reverse
+fn train_leading_car(
+    cars: &[TrainCarState],
+    direction: f32,
+) -> Option<&TrainCarState> {
+    if direction > 0.0 {
+        cars.first()
+    } else {
+        cars.last()
+    }
+}
+
+// train name and car index holding a car entity
+fn find_train_car(
+    world_state: &WorldState,
+    car_entity: Entity,
+) -> Option<(String, usize)> {
+    world_state.animatable_trains.iter().find_map(|(train_name, train_state)| {
+        train_state.cars.iter().position(|car| car.entity == Some(car_entity))
+            .map(|car_idx| (train_name.clone(), car_idx))
+    })
+}
+
+// drives timetabled trains between stations, obeying signals
+fn update_trains_autopilot(
+    time: Res<Time>,
+    cursor_lock_state: Res<CursorLockState>,
+    rails_state: Res<RailsState>,
+    mut world_state: ResMut<WorldState>,
+    mut door_events: EventWriter<DoorEvent>,
+) {
+    if !cursor_lock_state.enabled {
+        return;
+    }
+
+    let active_train = world_state.active_train.clone();
+    for (train_name, train_state) in world_state.animatable_trains.iter_mut() {
+        // the player overrides the autopilot while driving
+        if active_train.as_ref() == Some(train_name) {
+            continue;
+        }
+        if train_state.cars[0].rail.is_none() {
+            continue;
+        }
+        let mut autopilot = match train_state.autopilot.take() {
+            Some(autopilot) if !autopilot.timetable.is_empty() => autopilot,
+            autopilot => {
+                train_state.autopilot = autopilot;
+                continue;
+            }
+        };
+
+        // hold brakes unless cleared to run below
+        train_state.throttle = 0;
+        train_state.braking = true;
+
+        if autopilot.dwell_rmn > 0.0 {
+            // standing at a station, depart when dwell is over
+            autopilot.dwell_rmn -= time.delta_seconds();
+            if autopilot.dwell_rmn <= 0.0 {
+                for door in train_state.cars.iter().flat_map(|car| car.doors.iter()) {
+                    door_events.send(DoorEvent {
+                        action: DoorEventAction::Close,
+                        door: door.clone(),
+                    });
+                }
+                autopilot.next_stop = (autopilot.next_stop + 1) % autopilot.timetable.len();
+            }
+        } else if let Some(station) = rails_state.stations.get(&autopilot.timetable[autopilot.next_stop].station) {
+            let stopped = train_state.speed.abs() < TRAIN_STOPPED_SPEED;
+            // measured from the car leading the way, searching the whole network
+            let leading_rail = |direction: f32| train_leading_car(&train_state.cars, direction)
+                .and_then(|car| car.rail.clone());
+            let station_distance = |direction: f32| leading_rail(direction).and_then(|rail| rails_state.distance_along(
+                &rail, rail.facing * direction > 0.0, &station.segment, station.distance, f32::INFINITY));
+
+            // turn back while stopped if the station lies behind
+            if stopped && station_distance(train_state.direction).is_none()
+                    && station_distance(-train_state.direction).is_some() {
+                train_state.direction = -train_state.direction;
+            }
+
+            if let Some(distance) = station_distance(train_state.direction) {
+                if distance <= AUTOPILOT_STOP_TOLERANCE && stopped {
+                    // arrived, open doors and wait
+                    autopilot.dwell_rmn = autopilot.timetable[autopilot.next_stop].dwell.max(f32::EPSILON);
+                    for door in train_state.cars.iter().flat_map(|car| car.doors.iter()) {
+                        door_events.send(DoorEvent {
+                            action: DoorEventAction::Open,
+                            door: door.clone(),
+                        });
+                    }
+                } else {
+                    // run to the nearer of station or end of authority, braking in time
+                    let authority = train_leading_car(&train_state.cars, train_state.direction)
+                        .and_then(|car| car.rail.as_ref().map(|leading_rail| (car, leading_rail)))
+                        .map_or(0.0, |(car, leading_rail)| {
+                            // signals and routes, then any train ahead inside the same segment
+                            let forward = leading_rail.facing * train_state.direction > 0.0;
+                            let leading_end = rails_state.offset(
+                                leading_rail, if train_state.direction > 0.0 { car.front } else { -car.rear });
+                            rails_state.authority_ahead(train_name, leading_rail, forward, AUTOPILOT_LOOKAHEAD)
+                                .min(rails_state.clearance_ahead(train_name, &leading_end, forward, AUTOPILOT_LOOKAHEAD))
+                        });
+                    let target = distance.min(authority);
+                    let dynamics = &train_state.dynamics;
+                    let deceleration = (dynamics.brake_force + dynamics.rolling_resistance).max(f32::EPSILON);
+                    let stopping_distance = train_state.speed * train_state.speed / (2.0 * deceleration);
+                    if target > stopping_distance + 0.5 * AUTOPILOT_STOP_TOLERANCE {
+                        train_state.braking = false;
+                        if train_state.speed.abs() < AUTOPILOT_CRUISE_RATIO * dynamics.max_speed {
+                            train_state.throttle = if target < AUTOPILOT_APPROACH_DISTANCE { 1 } else { dynamics.max_notch };
+                        }
+                    }
+                }
+            }
+        }
+
+        train_state.autopilot = Some(autopilot);
+    }
 }
 
 fn update_trains_movement(
+    time: Res<Time>,
     cursor_lock_state: Res<CursorLockState>,
+    key_state: Res<KeyInputState>,
+    rapier_conf: Res<RapierConfiguration>,
+    mut rails_state: ResMut<RailsState>,
     mut world_state: ResMut<WorldState>,
-    mut transforms: Query<(Entity, &mut Transform)>,
+    mut trains: Query<(&Transform, &mut Velocity)>,
     mut sounds_events: EventWriter<SoundsEvent>,
-    keyboard_input: Res<Input<KeyCode>>,
-    mover_query: Query<(Entity, &Mover)>,
+    mut train_events: EventWriter<TrainsEvent>,
 ) {
     if !cursor_lock_state.enabled {
         return;
     }
 
-    if world_state.active_train.is_some() {
-        let train_name = world_state.active_train.as_ref().unwrap().clone();
-        if let Some(train_state) = world_state.animatable_trains.get_mut(&train_name) {
-            let mut train_transform = transforms.get_mut(train_state.parent_entity.unwrap()).unwrap().1;
-            let mut amove = 0.0;
-            if keyboard_input.pressed(KeyCode::W) {
-                amove = -0.02;
+    let active_train = world_state.active_train.clone();
+    for (train_name, train_state) in world_state.animatable_trains.iter_mut() {
+        // apply driver controls to the active train
+        if active_train.as_ref() == Some(train_name) {
+            if key_state.throttle_up {
+                train_state.throttle = (train_state.throttle + 1).min(train_state.dynamics.max_notch);
             }
-            if keyboard_input.pressed(KeyCode::S) {
-                amove = 0.02;
+            if key_state.throttle_down {
+                train_state.throttle = (train_state.throttle - 1).max(0);
             }
-            train_transform.translation += amove * Vec3::Z;
+            // reverser only moves while stopped
+            if key_state.toggle_reverser && train_state.speed.abs() < TRAIN_STOPPED_SPEED {
+                train_state.direction = -train_state.direction;
+            }
+            train_state.braking = key_state.brake;
+            // exit key returns control to the mover
+            if key_state.exit_train {
+                train_events.send(TrainsEvent {
+                    action: TrainsEventAction::StopControl,
+                    train: train_name.clone(),
+                });
+            }
+        }
+
+        train_state.speed = integrate_train_speed(train_state, time.delta_seconds());
+
+        // kinematic velocity moves each car and anything riding it
+        let dt = physics_step_dt(&time, &rapier_conf);
+        if train_state.cars[0].rail.is_some() {
+            advance_train(&rails_state, train_name, train_state, train_state.speed * dt);
 
-            if amove.abs() >= f32::EPSILON {
-                if !train_state.running {
-                    train_state.running = true;
+            for car in train_state.cars.iter() {
+                let car_rail = car.rail.as_ref().unwrap();
+                let (point, tangent) = rails_state.point_at(car_rail);
+                let car_facing = if car.flipped { -car_rail.facing } else { car_rail.facing };
+                let (car_transform, mut car_velocity) = trains.get_mut(car.entity.unwrap()).unwrap();
+                if dt > f32::EPSILON {
+                    let target_rotation = Transform::default()
+                        .looking_at(tangent * car_facing, Vec3::Y).rotation;
+                    let mut delta_rotation = target_rotation * car_transform.rotation.inverse();
+                    if delta_rotation.w < 0.0 {
+                        delta_rotation = -delta_rotation;
+                    }
+                    car_velocity.linvel = (point - car_transform.translation) / dt;
+                    car_velocity.angvel = delta_rotation.to_scaled_axis() / dt;
+                }
+            }
+        } else {
+            // free running cars keep to the leading car forward
+            let lead_car = &train_state.cars[0];
+            let forward = trains.get(lead_car.entity.unwrap()).unwrap().0.forward()
+                * if lead_car.flipped { -1.0 } else { 1.0 };
+            for car in train_state.cars.iter() {
+                let (_, mut car_velocity) = trains.get_mut(car.entity.unwrap()).unwrap();
+                car_velocity.linvel = train_state.speed * forward;
+            }
+        }
+
+        // rolling sound follows speed
+        let speed_ratio = train_state.speed.abs() / train_state.dynamics.max_speed.max(f32::EPSILON);
+        if train_state.speed.abs() >= TRAIN_STOPPED_SPEED {
+            if !train_state.running {
+                train_state.running = true;
+                sounds_events.send(SoundsEvent {
+                    action: SoundsEventAction::Resume,
+                    name: train_sound_name(train_name),
+                });
+                if let Some(lead_entity) = train_state.cars[0].entity {
                     sounds_events.send(SoundsEvent {
-                        action: SoundsEventAction::Resume,
-                        name: "train".into(),
+                        action: SoundsEventAction::Follow(lead_entity),
+                        name: train_sound_name(train_name),
                     });
                 }
+            }
+            sounds_events.send(SoundsEvent {
+                action: SoundsEventAction::SetVolume(
+                    TRAIN_SOUND_MIN_VOLUME + (1.0 - TRAIN_SOUND_MIN_VOLUME) * speed_ratio),
+                name: train_sound_name(train_name),
+            });
+            sounds_events.send(SoundsEvent {
+                action: SoundsEventAction::SetPlaybackRate(
+                    TRAIN_SOUND_MIN_RATE + (1.0 - TRAIN_SOUND_MIN_RATE) * speed_ratio),
+                name: train_sound_name(train_name),
+            });
+        } else if train_state.running {
+            train_state.running = false;
+            sounds_events.send(SoundsEvent {
+                action: SoundsEventAction::Pause,
+                name: train_sound_name(train_name),
+            });
+        }
+    }
+
+    update_train_occupancy(&mut rails_state, &world_state);
+}
+
+// moves a train along its rails, the car leading the way checks the interlocking
+// and stops short of other trains, the rest follow at coupler spacing
+fn advance_train(
+    rails_state: &RailsState,
+    train_name: &str,
+    train_state: &mut WorldTrainState,
+    travel: f32,
+) {
+    let anchor = if travel >= 0.0 { 0 } else { train_state.cars.len() - 1 };
+    let anchor_car = &train_state.cars[anchor];
+    let anchor_rail = anchor_car.rail.as_ref().unwrap();
+    let leading_end = rails_state.offset(anchor_rail, if travel >= 0.0 { anchor_car.front } else { -anchor_car.rear });
+    let clearance = rails_state.clearance_ahead(
+        train_name, &leading_end, (travel >= 0.0) == (leading_end.facing > 0.0), travel.abs());
+    let (anchor_rail, stopped) = rails_state.advance(train_name, anchor_rail, clearance.copysign(travel));
+    if stopped || clearance < travel.abs() {
+        train_state.speed = 0.0;
+    }
+    train_state.cars[anchor].rail = Some(anchor_rail);
+    place_train_cars(rails_state, &mut train_state.cars, anchor);
+}
 
-                let (mover_ent, _mover) = mover_query.single();
-                let mut mover_transform = transforms.get_mut(mover_ent).unwrap().1;
-                mover_transform.translation += amove * Vec3::Z;
+// rebuilds per-segment occupancy from the track under every car, coupler end to coupler end
+fn update_train_occupancy(
+    rails_state: &mut RailsState,
+    world_state: &WorldState,
+) {
+    rails_state.occupancy.clear();
+    for (train_name, train_state) in world_state.animatable_trains.iter() {
+        for car in train_state.cars.iter() {
+            if let Some(rail) = &car.rail {
+                let rear = rails_state.offset(rail, -car.rear);
+                rails_state.occupy(train_name, &rear, car.front + car.rear);
             }
-            if amove.abs() < f32::EPSILON && train_state.running {
-                train_state.running = false;
-                sounds_events.send(SoundsEvent {
-                    action: SoundsEventAction::Pause,
-                    name: "train".into(),
-                });
+        }
+    }
+}
+
+// integrates train speed over dt from throttle, brake and rolling resistance
+fn integrate_train_speed(
+    train_state: &WorldTrainState,
+    dt: f32,
+) -> f32 {
+    let dynamics = &train_state.dynamics;
+    let throttle_ratio = if dynamics.max_notch > 0 {
+        train_state.throttle as f32 / dynamics.max_notch as f32
+    } else { 0.0 };
+    let traction = dynamics.acceleration * throttle_ratio * train_state.direction;
+    let mut speed = train_state.speed + traction * dt;
+
+    // resistance opposes motion but never reverses it
+    let resistance = dynamics.rolling_resistance +
+        if train_state.braking { dynamics.brake_force } else { 0.0 };
+    let drag = (resistance * dt).min(speed.abs());
+    speed -= speed.signum() * drag;
+
+    speed.clamp(-dynamics.max_speed, dynamics.max_speed)
+}
+
+fn update_trains_hud(
+    trains_state: Res<TrainsState>,
+    world_state: Res<WorldState>,
+    input_map: Res<KeyInputMap>,
+    mut vis_query: Query<&mut Visibility>,
+    mut text_query: Query<&mut Text, With<TrainsHudText>>,
+) {
+    let train_state = world_state.active_train.as_ref()
+        .and_then(|train_name| world_state.animatable_trains.get(train_name));
+
+    // show hud only while driving
+    if let Some(hud_entity) = trains_state.hud_entity {
+        if let Ok(mut vis) = vis_query.get_mut(hud_entity) {
+            if vis.is_visible != train_state.is_some() {
+                vis.is_visible = train_state.is_some();
             }
         }
     }
+
+    if let Some(train_state) = train_state {
+        let mut text = text_query.single_mut();
+        text.sections[0].value = format!(
+            "Speed {:.0} km/h\nThrottle {}/{}{}\nDirection {}\n\n{:?}/{:?} throttle  {:?} brake  {:?} reverser  {:?} exit",
+            train_state.speed.abs() * 3.6,
+            train_state.throttle,
+            train_state.dynamics.max_notch,
+            if train_state.braking { "  BRAKE" } else { "" },
+            if train_state.direction > 0.0 { "Forward" } else { "Reverse" },
+            input_map.key_forward,
+            input_map.key_backward,
+            input_map.key_jump,
+            input_map.key_reverser,
+            input_map.key_exit_train,
+        );
+    }
+}
+
+fn exit_trains_hud(
+    mut commands: Commands,
+    trains_state: Res<TrainsState>,
+) {
+    if let Some(hud_entity) = trains_state.hud_entity {
+        commands.entity(hud_entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{RailPosition,RailSegmentState};
+
+    // two cars coupled on a straight line, the train facing increasing distance
+    fn coupled_train() -> (RailsState, WorldState) {
+        let mut rails_state = RailsState::default();
+        rails_state.segments.insert("line".into(), RailSegmentState::new(
+            vec![Vec3::ZERO, Vec3::new(0.0, 0.0, 100.0)], vec![], vec![]));
+        let mut cars = (0..2).map(|idx| TrainCarState {
+            entity: Some(Entity::from_raw(idx)),
+            front: 5.0,
+            rear: 5.0,
+            ..Default::default()
+        }).collect::<Vec<TrainCarState>>();
+        cars[0].rail = Some(RailPosition { segment: "line".into(), distance: 50.0, facing: 1.0 });
+        place_train_cars(&rails_state, &mut cars, 0);
+
+        let mut world_state = WorldState::default();
+        world_state.animatable_trains.insert("train01".into(), WorldTrainState {
+            direction: 1.0,
+            cars,
+            ..Default::default()
+        });
+        update_train_occupancy(&mut rails_state, &world_state);
+        (rails_state, world_state)
+    }
+
+    fn car_distance(world_state: &WorldState, train_name: &str) -> f32 {
+        world_state.animatable_trains[train_name].cars[0].rail.as_ref().unwrap().distance
+    }
+
+    #[test]
+    fn uncoupled_halves_hold_their_own_track() {
+        let (mut rails_state, mut world_state) = coupled_train();
+        assert_eq!(rails_state.occupancy["line"].len(), 1);
+
+        uncouple_train_car(&mut world_state, Entity::from_raw(0));
+        update_train_occupancy(&mut rails_state, &world_state);
+        assert_eq!(world_state.animatable_trains["train01"].cars.len(), 1);
+        assert_eq!(world_state.animatable_trains["train01.1"].cars.len(), 1);
+        let spans = &rails_state.occupancy["line"];
+        assert_eq!(spans.len(), 2);
+        assert!(spans["train01.1"].1 < spans["train01"].0);
+    }
+
+    #[test]
+    fn uncoupled_half_stops_against_the_other() {
+        let (mut rails_state, mut world_state) = coupled_train();
+        uncouple_train_car(&mut world_state, Entity::from_raw(0));
+        update_train_occupancy(&mut rails_state, &world_state);
+        let start = car_distance(&world_state, "train01.1");
+
+        // the rear half backs away freely
+        let rear_half = world_state.animatable_trains.get_mut("train01.1").unwrap();
+        rear_half.speed = -2.0;
+        advance_train(&rails_state, "train01.1", rear_half, -2.0);
+        assert_eq!(rear_half.speed, -2.0);
+        update_train_occupancy(&mut rails_state, &world_state);
+        assert!((car_distance(&world_state, "train01.1") - (start - 2.0)).abs() < 1e-4);
+
+        // then drives back onto the front half, stopping with the couplers touching
+        let rear_half = world_state.animatable_trains.get_mut("train01.1").unwrap();
+        rear_half.speed = 5.0;
+        advance_train(&rails_state, "train01.1", rear_half, 5.0);
+        assert_eq!(rear_half.speed, 0.0);
+        update_train_occupancy(&mut rails_state, &world_state);
+        assert!((car_distance(&world_state, "train01.1") - (start + TRAIN_COUPLER_GAP)).abs() < 1e-4);
+
+        // the front half still pulls away
+        let front_half = world_state.animatable_trains.get_mut("train01").unwrap();
+        front_half.speed = 2.0;
+        advance_train(&rails_state, "train01", front_half, 2.0);
+        assert_eq!(front_half.speed, 2.0);
+        assert!((car_distance(&world_state, "train01") - 52.0).abs() < 1e-4);
+    }
 }
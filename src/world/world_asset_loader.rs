@@ -6,6 +6,7 @@ use bevy::{
 };
 // use bevy_rapier3d::prelude::*;
 use serde::Deserialize;
+use std::collections::HashMap;
 
 #[derive(Debug, Deserialize, TypeUuid)]
 #[uuid = "39cadc56-aa9c-4543-8640-a008b74b4962"]
@@ -17,6 +18,240 @@ pub struct WorldAsset {
     pub props: Vec<WorldProp>,
     pub sounds: Vec<WorldSound>,
     pub trains: Vec<WorldTrain>,
+    #[serde(default)]
+    pub rails: Vec<WorldRail>,
+    #[serde(default)]
+    pub rail_switches: Vec<WorldRailSwitch>,
+    #[serde(default)]
+    pub rail_signals: Vec<WorldRailSignal>,
+    #[serde(default)]
+    pub stations: Vec<WorldStation>,
+    #[serde(default)]
+    pub movers: Vec<WorldMover>,
+    #[serde(default)]
+    pub environment: WorldEnvironment,
+    #[serde(default)]
+    pub feedback_sounds: WorldFeedbackSounds,
+    #[serde(default)]
+    pub music: WorldMusic,
+    #[serde(default)]
+    pub footsteps: WorldFootsteps,
+    // echoes outside of any zone
+    #[serde(default)]
+    pub echoes: WorldEchoes,
+    #[serde(default)]
+    pub echo_zones: Vec<WorldEchoZone>,
+    #[serde(default)]
+    pub traversals: Vec<WorldTraversal>,
+    #[serde(default)]
+    pub power_networks: Vec<WorldPowerNetwork>,
+}
+
+// power fed through a flag, overloaded while more lights and movers draw on it than its capacity
+#[derive(Clone, Debug, Deserialize)]
+pub struct WorldPowerNetwork {
+    pub flag: String,
+    pub capacity: u32,
+}
+
+// ladder, ledge or vault volume, scale holds the half extents
+// the volume faces along rotation * -Z, into the wall or obstacle, its top is the top of the climb
+#[derive(Clone, Debug, Deserialize)]
+pub struct WorldTraversal {
+    pub kind: String,
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3,
+}
+
+// delayed replays of one-shot sfx, not a reverb on the sfx bus
+// reflections is the level of the first replays, spaced by reflection_spacing seconds
+// echo repeats after echo_delay seconds, each repeat echo_feedback of the last
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WorldEchoes {
+    pub reflections: f32,
+    pub reflection_spacing: f32,
+    pub echo_delay: f32,
+    pub echo_feedback: f32,
+}
+
+impl Default for WorldEchoes {
+    fn default() -> Self {
+        Self {
+            reflections: 0.0,
+            reflection_spacing: 0.05,
+            echo_delay: 0.0,
+            echo_feedback: 0.0,
+        }
+    }
+}
+
+// box zone, scale holds the half extents, echoes blend in over blend meters outside it
+// looped and ambient channels play dry in every zone
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WorldEchoZone {
+    pub name: String,
+    pub translation: Vec3,
+    pub scale: Vec3,
+    pub blend: f32,
+    pub echoes: WorldEchoes,
+}
+
+impl Default for WorldEchoZone {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            translation: Vec3::ZERO,
+            scale: Vec3::ONE,
+            blend: 2.0,
+            echoes: WorldEchoes::default(),
+        }
+    }
+}
+
+// footstep sound per surface, surface is the one for untagged ground
+// tagged surfaces without a sound step as the world surface
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WorldFootsteps {
+    pub surface: String,
+    pub sounds: HashMap<String, String>,
+}
+
+impl Default for WorldFootsteps {
+    fn default() -> Self {
+        Self {
+            surface: "snow".into(),
+            sounds: HashMap::from([
+                ("snow".into(), "steps_snow_dry".into()),
+                ("metal_grating".into(), "steps_metal_grating".into()),
+                ("concrete".into(), "steps_concrete".into()),
+                ("wood".into(), "steps_wood".into()),
+            ]),
+        }
+    }
+}
+
+// looping music and ambience tracks, zones swap them while the player is inside
+// fade is the crossfade in seconds
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WorldMusic {
+    pub music: Option<String>,
+    pub ambience: Option<String>,
+    pub fade: f32,
+    pub zones: Vec<WorldMusicZone>,
+}
+
+impl Default for WorldMusic {
+    fn default() -> Self {
+        Self {
+            music: None,
+            ambience: None,
+            fade: 3.0,
+            zones: Vec::new(),
+        }
+    }
+}
+
+// box zone, scale holds the half extents, the smallest zone around the player wins
+#[derive(Clone, Debug, Deserialize)]
+pub struct WorldMusicZone {
+    pub name: String,
+    pub translation: Vec3,
+    pub scale: Vec3,
+    #[serde(default)]
+    pub music: Option<String>,
+    #[serde(default)]
+    pub ambience: Option<String>,
+}
+
+// sounds played on interaction when nothing more specific is set, each world opts in
+// silent by default, there are no dedicated feedback sounds among the assets yet
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct WorldFeedbackSounds {
+    pub pickup: Option<String>,
+    pub blocked: Option<String>,
+    pub door_open: Option<String>,
+    pub door_close: Option<String>,
+    pub door_locked: Option<String>,
+    pub light_switch: Option<String>,
+}
+
+// sky, ambient and sun settings for a world
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WorldEnvironment {
+    pub ambient_color: Color,
+    pub ambient_brightness: f32,
+    pub clear_color: Color,
+    // far plane in meters, nothing past it is drawn and the clear color shows instead
+    pub view_distance: Option<f32>,
+    pub sun: Option<WorldSun>,
+    pub day_night: Option<WorldDayNight>,
+}
+
+impl Default for WorldEnvironment {
+    fn default() -> Self {
+        Self {
+            ambient_color: Color::WHITE,
+            ambient_brightness: 0.05,
+            clear_color: Color::rgb(0.2, 0.2, 0.2),
+            view_distance: None,
+            sun: None,
+            day_night: None,
+        }
+    }
+}
+
+// direction the sunlight travels at noon, illuminance in lux
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WorldSun {
+    pub direction: Vec3,
+    pub color: Color,
+    pub illuminance: f32,
+    pub shadows: bool,
+}
+
+impl Default for WorldSun {
+    fn default() -> Self {
+        Self {
+            direction: Vec3::new(-0.3, -1.0, -0.2),
+            color: Color::WHITE,
+            illuminance: 10000.0,
+            shadows: true,
+        }
+    }
+}
+
+// period in seconds for a full day, times in hours
+// night scales ambient and takes the night clear color, dusk lights switch on from dusk to dawn
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WorldDayNight {
+    pub period: f32,
+    pub start: f32,
+    pub dusk: f32,
+    pub dawn: f32,
+    pub night_ambient: f32,
+    pub night_clear_color: Color,
+}
+
+impl Default for WorldDayNight {
+    fn default() -> Self {
+        Self {
+            period: 600.0,
+            start: 12.0,
+            dusk: 19.0,
+            dawn: 6.0,
+            night_ambient: 0.2,
+            night_clear_color: Color::rgb(0.02, 0.02, 0.05),
+        }
+    }
 }
 
 // represents data for convex colliders defined for a world
@@ -26,6 +261,9 @@ pub struct WorldCollider {
     pub translation: Vec3,
     pub rotation: Quat,
     pub scale: Vec3,
+    // snow, metal_grating, concrete or wood, for footsteps
+    #[serde(default)]
+    pub surface: Option<String>,
 }
 
 // represents door
@@ -36,6 +274,51 @@ pub struct WorldDoor {
     pub translation: Vec3,
     pub rotation: Quat,
     pub scale: Vec3,
+    #[serde(default)]
+    pub hinge: WorldDoorHinge,
+    #[serde(default)]
+    pub behaviour: WorldDoorBehaviour,
+    // replaces the panel collider with one built from the door meshes
+    #[serde(default)]
+    pub auto_collider: Option<WorldAutoCollider>,
+}
+
+// door locking and automatic opening, times in seconds and distances in meters
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct WorldDoorBehaviour {
+    pub locked: bool,
+    // blocker that unlocks the door, "holding.<item>" or "flag_enabled.<flag>"
+    pub key: Option<String>,
+    pub auto_close: Option<f32>,
+    // opens while the player is within range
+    pub proximity: Option<f32>,
+    // opens only from the door +Z side
+    pub one_way: bool,
+    pub open_sound: Option<String>,
+    pub close_sound: Option<String>,
+    pub locked_sound: Option<String>,
+}
+
+// door hinge in the unscaled door frame, angle in degrees and speed in degrees/s
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WorldDoorHinge {
+    pub pivot: Vec3,
+    pub axis: Vec3,
+    pub angle: f32,
+    pub speed: f32,
+}
+
+impl Default for WorldDoorHinge {
+    fn default() -> Self {
+        Self {
+            pivot: Vec3::new(-0.8, 0.0, 0.0),
+            axis: Vec3::Y,
+            angle: 90.0,
+            speed: 120.0,
+        }
+    }
 }
 
 // represents interactable target collider
@@ -54,6 +337,17 @@ pub struct WorldInteraction {
     pub interaction_text: String,
     pub actions: Vec<(String, String)>,
     pub blockers: Vec<(String, String)>,
+    #[serde(default)]
+    pub feedback: WorldInteractionFeedback,
+}
+
+// overrides the world feedback sounds for one interactable
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct WorldInteractionFeedback {
+    pub sound: Option<String>,
+    pub blocked_sound: Option<String>,
+    pub mute: bool,
 }
 
 // represents gltf prop
@@ -64,6 +358,27 @@ pub struct WorldProp {
     pub rotation: Quat,
     pub scale: Vec3,
     pub animatable: Option<String>,
+    #[serde(default)]
+    pub auto_collider: Option<WorldAutoCollider>,
+}
+
+// collider generated from gltf meshes, shape is aabb, convex_hull or convex_decomposition
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WorldAutoCollider {
+    pub shape: String,
+    pub memberships: u32,
+    pub filters: u32,
+}
+
+impl Default for WorldAutoCollider {
+    fn default() -> Self {
+        Self {
+            shape: "aabb".into(),
+            memberships: 0b0001,
+            filters: 0b0001,
+        }
+    }
 }
 
 // represents 3d positioned sound
@@ -73,6 +388,44 @@ pub struct WorldSound {
     pub translation: Vec3,
     pub paused: bool,
     pub animatable: Option<String>,
+    #[serde(default)]
+    pub spatial: WorldSoundSpatial,
+    // mixer bus, ambience when unset
+    #[serde(default)]
+    pub bus: Option<String>,
+}
+
+// attenuation from min_distance out to range, rolloff is linear, inverse or exponential
+// volume stays within min_volume and max_volume, doppler shifts pitch for moving emitters
+// occlusion is the share of volume heard through walls, 1.0 ignores walls
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WorldSoundSpatial {
+    pub range: f32,
+    pub min_distance: f32,
+    pub rolloff: String,
+    pub rolloff_factor: f32,
+    pub min_volume: f32,
+    pub max_volume: f32,
+    pub doppler: bool,
+    pub doppler_factor: f32,
+    pub occlusion: f32,
+}
+
+impl Default for WorldSoundSpatial {
+    fn default() -> Self {
+        Self {
+            range: 10.0,
+            min_distance: 0.0,
+            rolloff: "linear".into(),
+            rolloff_factor: 1.0,
+            min_volume: 0.0,
+            max_volume: 1.0,
+            doppler: false,
+            doppler_factor: 1.0,
+            occlusion: 0.3,
+        }
+    }
 }
 
 // represents light
@@ -84,6 +437,66 @@ pub struct WorldLight {
     pub scale: Vec3,
     pub watts: f32,
     pub animatable: Option<String>,
+    // intensity when switched on, for lights starting off at 0 watts
+    #[serde(default)]
+    pub on_watts: Option<f32>,
+    #[serde(default)]
+    pub behaviour: Option<WorldLightBehaviour>,
+    #[serde(default)]
+    pub emissive: Option<WorldLightEmissive>,
+    // switched on from dusk to dawn by the day/night cycle
+    #[serde(default)]
+    pub dusk: bool,
+}
+
+// lamp mesh glowing with the light, a node or material within an animatable prop
+// strength scales the light color at full intensity
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WorldLightEmissive {
+    pub prop: String,
+    pub node: Option<String>,
+    pub material: Option<String>,
+    pub strength: f32,
+}
+
+impl Default for WorldLightEmissive {
+    fn default() -> Self {
+        Self {
+            prop: "".into(),
+            node: None,
+            material: None,
+            strength: 1.0,
+        }
+    }
+}
+
+// light pattern, steady, flicker, strobe, pulse or brown_out, rate in cycles/s
+// min is the dimmest share of intensity, flag limits the pattern to while it is enabled
+// brown_out lights draw on the power network of their power flag, dark without it and sagging while it is overloaded
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WorldLightBehaviour {
+    pub pattern: String,
+    pub rate: f32,
+    pub min: f32,
+    pub flag: Option<String>,
+    pub power: Option<String>,
+    // defaults to one picked from the light position so lights fall out of step
+    pub seed: Option<u64>,
+}
+
+impl Default for WorldLightBehaviour {
+    fn default() -> Self {
+        Self {
+            pattern: "flicker".into(),
+            rate: 4.0,
+            min: 0.1,
+            flag: None,
+            power: None,
+            seed: None,
+        }
+    }
 }
 
 // represents train prop
@@ -94,8 +507,141 @@ pub struct WorldTrain {
     pub rotation: Quat,
     pub scale: Vec3,
     pub animatable: Option<String>,
+    #[serde(default)]
+    pub dynamics: WorldTrainDynamics,
+    // rail segment the train starts on, free running if none
+    #[serde(default)]
+    pub rail: Option<String>,
+    // station stops for autopilot, player driven if empty
+    #[serde(default)]
+    pub timetable: Vec<WorldTimetableStop>,
+    // cars coupled behind the leading prop, front to back
+    #[serde(default)]
+    pub cars: Vec<WorldTrainCar>,
+}
+
+// represents train car sub-world, flipped cars face the rear
+#[derive(Clone, Debug, Deserialize)]
+pub struct WorldTrainCar {
+    pub prop: String,
+    #[serde(default)]
+    pub flipped: bool,
+}
+
+// represents station stop on a train timetable, dwell in seconds
+#[derive(Clone, Debug, Deserialize)]
+pub struct WorldTimetableStop {
+    pub station: String,
+    pub dwell: f32,
+}
+
+// train handling parameters, speeds in m/s and forces in m/s^2
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WorldTrainDynamics {
+    pub max_notch: i32,
+    pub acceleration: f32,
+    pub brake_force: f32,
+    pub max_speed: f32,
+    pub rolling_resistance: f32,
+}
+
+impl Default for WorldTrainDynamics {
+    fn default() -> Self {
+        Self {
+            max_notch: 4,
+            acceleration: 1.2,
+            brake_force: 3.0,
+            max_speed: 8.0,
+            rolling_resistance: 0.15,
+        }
+    }
+}
+
+// represents directed rail segment, next segments start where this one ends
+#[derive(Debug, Deserialize)]
+pub struct WorldRail {
+    pub name: String,
+    pub points: Vec<Vec3>,
+    #[serde(default)]
+    pub next: Vec<String>,
+    #[serde(default)]
+    pub prev: Vec<String>,
+}
+
+// represents switch points joining a trunk segment to diverging branches
+#[derive(Debug, Deserialize)]
+pub struct WorldRailSwitch {
+    pub name: String,
+    pub trunk: String,
+    pub branches: Vec<String>,
+}
+
+// represents signal guarding the exit at one end of a segment
+#[derive(Debug, Deserialize)]
+pub struct WorldRailSignal {
+    pub name: String,
+    pub segment: String,
+    pub forward: bool,
+    pub translation: Vec3,
+}
+// represents station platform stop point on a rail segment
+#[derive(Debug, Deserialize)]
+pub struct WorldStation {
+    pub name: String,
+    pub segment: String,
+    pub translation: Vec3,
 }
 
+// represents kinematic body carrying props and colliders along waypoints
+#[derive(Debug, Deserialize)]
+pub struct WorldMover {
+    pub name: String,
+    pub translation: Vec3,
+    pub rotation: Quat,
+    // poses relative to the mover, it rests at the first
+    pub waypoints: Vec<WorldMoverWaypoint>,
+    #[serde(default)]
+    pub motion: WorldMoverMotion,
+    #[serde(default)]
+    pub props: Vec<WorldProp>,
+    #[serde(default)]
+    pub colliders: Vec<WorldCollider>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct WorldMoverWaypoint {
+    pub translation: Vec3,
+    #[serde(default)]
+    pub rotation: Quat,
+}
+
+// mover path timing, speed in m/s and angular speed in degrees/s
+// easing is linear, ease_in, ease_out or ease_in_out, mode is once, loop or ping_pong
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WorldMoverMotion {
+    pub speed: f32,
+    pub angular_speed: f32,
+    pub easing: String,
+    pub mode: String,
+    pub autostart: bool,
+    // flag powering the mover
+    pub power: Option<String>,
+}
+
+impl Default for WorldMoverMotion {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            angular_speed: 45.0,
+            easing: "linear".into(),
+            mode: "once".into(),
+            autostart: false,
+            power: None,
+        }
+    }
+}
 
 #[derive(Default)]
 pub struct WorldAssetLoader;
@@ -3,8 +3,10 @@ use crate::loading::{LoadingUiEvent,LoadingUiEventAction,WorldAssets,
 use crate::game_state::GameState;
 use crate::movement::Mover;
 use crate::settings::SettingsAsset;
-use crate::world::{DoorState,InteractableState,WorldAsset,WorldState,
-    WorldSoundState,WorldTrainState,AnimatableState};
+use crate::world::{AutoCollider,AutoColliderSource,DoorState,EnvironmentState,InteractableState,MoversState,RailPosition,RailSegmentState,
+    RailSignalState,RailStationState,RailSwitchState,RailsState,Surface,TrainAutopilot,
+    TrainCarState,WorldAsset,WorldAutoCollider,WorldDoor,WorldFeedbackSounds,WorldState,WorldSoundState,WorldTrainCar,WorldTrainState,
+    WorldMoverState,AnimatableState,light_state,setup_environment,place_train_cars,train_car_spacing};
 use bevy::prelude::*;
 use bevy::scene::InstanceId;
 use bevy_rapier3d::prelude::*;
@@ -84,6 +86,9 @@ fn setup_world_loading(
     world_handles: Res<WorldAssets>,
     mut world_loading: ResMut<WorldLoadingState>,
     mut world_state: ResMut<WorldState>,
+    mut rails_state: ResMut<RailsState>,
+    mut movers_state: ResMut<MoversState>,
+    mut environment_state: ResMut<EnvironmentState>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     settings: Res<SettingsAsset>,
@@ -99,6 +104,8 @@ fn setup_world_loading(
     world_state.animatable_trains = HashMap::new();
     world_state.doors = HashMap::new();
     world_state.active_train = None;
+    *rails_state = RailsState::default();
+    *movers_state = MoversState::default();
 
     let world_asset = if world_state.active_world == "world03" {
         world_assets.get(&world_handles.world03).unwrap()
@@ -106,51 +113,26 @@ fn setup_world_loading(
         world_assets.get(&world_handles.world01).unwrap()
     };
 
+    world_state.feedback_sounds = world_asset.feedback_sounds.clone();
+    world_state.music = world_asset.music.clone();
+    world_state.footsteps = world_asset.footsteps.clone();
+    world_state.echoes = world_asset.echoes.clone();
+    world_state.echo_zones = world_asset.echo_zones.clone();
+    world_state.traversals = world_asset.traversals.clone();
+    world_state.power_networks = world_asset.power_networks.clone();
+
+    // sky, ambient and sun
+    setup_environment(&mut commands, &mut environment_state, &world_asset.environment);
+
     // load props
     for data in world_asset.props.iter() {
-        let prop_handle: Option<Handle<Scene>> = match data.prop.as_str() {
-            "big_switch" => Some(world_props.big_switch.clone()),
-            "bottle_lightfuel" => Some(world_props.bottle_lightfuel.clone()),
-            "cardboard_closed" => Some(world01_props.cardboard_closed.clone()),
-            "cardboard_opened" => Some(world01_props.cardboard_opened.clone()),
-            "cardboard_tube" => Some(world01_props.cardboard_tube.clone()),
-            "city_fence" => Some(world_props.city_fence.clone()),
-            "diesel_generator" => Some(world_props.diesel_generator.clone()),
-            "door_blue" => Some(world_props.door_blue.clone()),
-            "fountain_round" => Some(world01_props.fountain_round.clone()),
-            "fuse_console" => Some(world_props.fuse_console.clone()),
-            "fuse_small" => Some(world_props.fuse_small.clone()),
-            "house_roof01" => Some(world01_props.house_roof01.clone()),
-            "house_woodside" => Some(world01_props.house_woodside.clone()),
-            "office_table" => Some(world01_props.office_desk01.clone()),
-            "office_desk" => Some(world01_props.office_desk02.clone()),
-            "office_chair" => Some(world01_props.office_chair.clone()),
-            "pallet" => Some(world01_props.pallet.clone()),
-            "rail_track" => Some(world_props.rail_track.clone()),
-            "train_wire" => Some(world_props.train_wire.clone()),
-            "tunnel_entrance" => Some(world_props.tunnel_entrance.clone()),
-            "world01_building01" => Some(world01_props.world01_building01.clone()),
-            "world01_generator_wire" => Some(world_props.world01_generator_wire.clone()),
-            "world01_ground01" => Some(world01_props.world01_ground01.clone()),
-            "world01_ground02" => Some(world_props.world01_ground02.clone()),
-            "world01_ground03" => Some(world_props.world01_ground03.clone()),
-            "refinery_column01" => Some(world03_props.refinery_column01.clone()),
-            "refinery_desalter" => Some(world03_props.refinery_desalter.clone()),
-            "refinery_scaffolding" => Some(world03_props.refinery_scaffolding.clone()),
-            "refinery_sphere" => Some(world03_props.refinery_sphere.clone()),
-            "refinery_tank01" => Some(world03_props.refinery_tank01.clone()),
-            "refinery_warmer" => Some(world03_props.refinery_warmer.clone()),
-            "world03_ground" => Some(world03_props.world03_ground.clone()),
-            "world03_pipes" => Some(world03_props.world03_pipes.clone()),
-            "world03_walkways" => Some(world03_props.world03_walkways.clone()),
-            _ => {
-                println!("Unknown prop! {:?}", data);
-                None
-            }
-        };
+        let prop_handle = prop_scene_handle(&data.prop, &world_props, &world01_props, &world03_props);
+        if prop_handle.is_none() {
+            println!("Unknown prop! {:?}", data);
+        }
         let mut prop_instance: Option<InstanceId> = None;
         if prop_handle.is_some() {
-            commands.spawn_bundle(SpatialBundle::from_transform(
+            let prop_entity = commands.spawn_bundle(SpatialBundle::from_transform(
                 Transform::from_translation(data.translation)
             ))
             .insert(WorldEntity)
@@ -159,9 +141,12 @@ fn setup_world_loading(
                     Transform::from_rotation(data.rotation)
                 )).id();
                 if settings.graphics_settings.render_mode.as_str() != "colliders" {
-                    prop_instance = Some(scene_spawner.spawn_as_child(prop_handle.unwrap(), parent));
+                    prop_instance = Some(scene_spawner.spawn_as_child(prop_handle.clone().unwrap(), parent));
                 }
-            });
+            }).id();
+            if let Some(auto_collider) = auto_collider(&data.auto_collider, prop_instance, prop_handle.clone(), data.rotation, None) {
+                commands.entity(prop_entity).insert(auto_collider);
+            }
         }
         if data.animatable.is_some() {
             if settings.graphics_settings.render_mode.as_str() != "colliders" {
@@ -178,9 +163,13 @@ fn setup_world_loading(
             _ => None
         };
         if shape_handle.is_some() {
-            commands
+            let mut collider = commands
                     .spawn_bundle(SpatialBundle::from_transform(
-                        Transform::from_translation(data.translation)))
+                        Transform::from_translation(data.translation)));
+            if let Some(surface) = &data.surface {
+                collider.insert(Surface { surface: surface.clone() });
+            }
+            collider
                     .insert(shape_handle.unwrap())
                     .insert(CollisionGroups::new(0b0001, 0b0001))
                     .insert(WorldEntity)
@@ -206,6 +195,7 @@ fn setup_world_loading(
             _ => None
         };
         let mut prop_instance: Option<InstanceId> = None;
+        let mut collider_entity: Option<Entity> = None;
         if prop_handle.is_some() {
             let parent_entity = commands.spawn_bundle(SpatialBundle::from_transform(
                 Transform::from_translation(data.translation)
@@ -216,7 +206,7 @@ fn setup_world_loading(
                     Transform::from_rotation(data.rotation)
                 )).id();
                 if settings.graphics_settings.render_mode.as_str() != "colliders" {
-                    prop_instance = Some(scene_spawner.spawn_as_child(prop_handle.unwrap(), parent));
+                    prop_instance = Some(scene_spawner.spawn_as_child(prop_handle.clone().unwrap(), parent));
                 }
 
                 let door_scale = data.scale * Vec3::new(0.8, 1.5, 0.05);
@@ -224,10 +214,11 @@ fn setup_world_loading(
                     "door_blue" => Some(Collider::cuboid(door_scale[0],door_scale[1],door_scale[2])),
                     _ => None
                 };
-                if shape_handle.is_some() {
-                    parent2
+                if shape_handle.is_some() && data.auto_collider.is_none() {
+                    collider_entity = Some(parent2
                             .spawn_bundle(SpatialBundle::from_transform(
-                                Transform::from_translation(door_scale[1] * Vec3::Y)))
+                                Transform::from_translation(door_scale[1] * Vec3::Y)
+                                    .with_rotation(data.rotation)))
                             .insert(shape_handle.unwrap())
                             .insert(CollisionGroups::new(0b0001, 0b0001))
 
@@ -241,10 +232,14 @@ fn setup_world_loading(
                                     });
                                 }
                             })
-                            ;
+                            .id());
                 }
             }).id();
-            world_state.doors.insert(data.name.to_string(), DoorState { parent_entity: Some(parent_entity), open: false });
+            world_state.doors.insert(data.name.to_string(), door_state(
+                data, &world_asset.feedback_sounds, Transform::from_translation(data.translation), Some(parent_entity), collider_entity));
+            if let Some(auto_collider) = auto_collider(&data.auto_collider, prop_instance, prop_handle.clone(), data.rotation, Some(data.name.clone())) {
+                commands.entity(parent_entity).insert(auto_collider);
+            }
         }
     }
 
@@ -303,8 +298,12 @@ fn setup_world_loading(
             .insert(WorldEntity)
             .id()
         };
-        if data.animatable.is_some() {
-            world_state.animatable_lights.insert(data.animatable.clone().unwrap(), light_entity);
+        // patterned lights without a name are keyed by entity
+        if data.animatable.is_some() || data.behaviour.is_some() || data.emissive.is_some() || data.dusk {
+            let name = data.animatable.clone().unwrap_or_else(|| format!("light.{}", light_entity.id()));
+            let emissive_instance = data.emissive.as_ref()
+                .and_then(|emissive| world_loading.animatable_scenes.get(&emissive.prop).copied());
+            world_state.animatable_lights.insert(name, light_state(data, light_entity, emissive_instance));
         }
     }
 
@@ -316,139 +315,504 @@ fn setup_world_loading(
                 sound: data.sound.clone(),
                 position: data.translation,
                 panning: 0.5,
-                volume: 0.5,
+                volume: 1.0,
+                playback_rate: 1.0,
                 paused: data.paused,
+                bus: data.bus.clone().unwrap_or_else(|| "ambience".into()),
+                spatial: Some(data.spatial.clone()),
+                ..default()
             });
         }
     }
 
+    // rails
+    for data in world_asset.rails.iter() {
+        rails_state.segments.insert(data.name.clone(),
+            RailSegmentState::new(data.points.clone(), data.next.clone(), data.prev.clone()));
+    }
+    for data in world_asset.rail_switches.iter() {
+        rails_state.switches.insert(data.name.clone(), RailSwitchState {
+            trunk: data.trunk.clone(),
+            branches: data.branches.clone(),
+            aligned: 0,
+        });
+    }
+    for data in world_asset.rail_signals.iter() {
+        let light_entity = commands.spawn_bundle(PointLightBundle {
+            transform: Transform::from_translation(data.translation),
+            point_light: PointLight {
+                intensity: 20.,
+                range: 2.,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(WorldEntity)
+        .id();
+        rails_state.signals.insert(data.name.clone(), RailSignalState {
+            segment: data.segment.clone(),
+            forward: data.forward,
+            light_entity: Some(light_entity),
+            clear: false,
+        });
+    }
+
+    // stations
+    for data in world_asset.stations.iter() {
+        if let Some(segment) = rails_state.segments.get(&data.segment) {
+            let distance = segment.project(data.translation);
+            rails_state.stations.insert(data.name.clone(), RailStationState {
+                segment: data.segment.clone(),
+                distance,
+            });
+        } else {
+            println!("unknown station segment :: {:?}", data);
+        }
+    }
+
+    // movers, kinematic bodies carrying their props and colliders
+    for data in world_asset.movers.iter() {
+        let mover_transform = Transform::from_translation(data.translation).with_rotation(data.rotation);
+        let waypoints = data.waypoints.iter().map(|waypoint| mover_transform.mul_transform(
+            Transform::from_translation(waypoint.translation).with_rotation(waypoint.rotation)))
+            .collect::<Vec<Transform>>();
+        if waypoints.is_empty() {
+            println!("unknown mover waypoints :: {:?}", data);
+            continue;
+        }
+        let mover_entity = commands
+            .spawn_bundle(SpatialBundle::from_transform(waypoints[0]))
+            .insert(WorldEntity)
+            .insert(RigidBody::KinematicVelocityBased)
+            .insert(Velocity::zero())
+            .with_children(|parent| {
+                for data in data.colliders.iter() {
+                    let shape_handle: Option<Collider> = match data.shape.as_str() {
+                        "cuboid" => Some(Collider::cuboid(data.scale[0],data.scale[1],data.scale[2])),
+                        _ => None
+                    };
+                    if let Some(shape_handle) = shape_handle {
+                        parent
+                                .spawn_bundle(SpatialBundle::from_transform(
+                                    Transform::from_translation(data.translation)))
+                                .insert(shape_handle)
+                                .insert(CollisionGroups::new(0b0001, 0b0001))
+                                .with_children(|parent| {
+                                    if settings.graphics_settings.render_mode.as_str() == "colliders" {
+                                        parent.spawn_bundle(PbrBundle {
+                                            mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
+                                            material: materials.add(Color::rgb(0.8, 0.7, 0.6).into()),
+                                            transform: Transform::from_scale(data.scale*2.0),
+                                            ..default()
+                                        });
+                                    }
+                                })
+                                ;
+                    } else {
+                        println!("unknown mover collider :: {:?}", data);
+                    }
+                }
+                for data in data.props.iter() {
+                    let prop_handle = prop_scene_handle(&data.prop, &world_props, &world01_props, &world03_props);
+                    if let Some(prop_handle) = prop_handle {
+                        let mut prop_instance: Option<InstanceId> = None;
+                        let mut prop_commands = parent.spawn_bundle(SpatialBundle::from_transform(
+                            Transform::from_translation(data.translation)
+                        ));
+                        prop_commands.with_children(|parent2| {
+                            let parent = parent2.spawn_bundle(SpatialBundle::from_transform(
+                                Transform::from_rotation(data.rotation)
+                            )).id();
+                            if settings.graphics_settings.render_mode.as_str() != "colliders" {
+                                prop_instance = Some(scene_spawner.spawn_as_child(prop_handle.clone(), parent));
+                            }
+                        });
+                        if let Some(auto_collider) = auto_collider(&data.auto_collider, prop_instance, Some(prop_handle), data.rotation, None) {
+                            prop_commands.insert(auto_collider);
+                        }
+                    } else {
+                        println!("Unknown prop! {:?}", data);
+                    }
+                }
+            })
+            .id();
+        movers_state.movers.insert(data.name.clone(), WorldMoverState {
+            entity: Some(mover_entity),
+            waypoints,
+            speed: data.motion.speed,
+            angular_speed: data.motion.angular_speed.to_radians(),
+            easing: data.motion.easing.clone(),
+            mode: data.motion.mode.clone(),
+            power: data.motion.power.clone(),
+            running: data.motion.autostart,
+            forward: true,
+            ..default()
+        });
+    }
+
     // trains
     // "denki_train" => Some(world_props.denki_train.clone()),
     for data in world_asset.trains.iter() {
-        let train_handle = Some(commands
-            .spawn_bundle(SpatialBundle::from_transform(
-                Transform::from_translation(data.translation)))
-                .insert(WorldEntity)
-                .with_children(|parent| {
-                    let world_handle: Option<Handle<WorldAsset>> = match data.prop.as_str() {
-                        "denki_train" => Some(world_handles.denki_train.clone()),
-                        _ => None
-                    };
-                    let train_asset = world_assets.get(&world_handle.unwrap()).unwrap();
-                    // colliders
-                    for data in train_asset.colliders.iter() {
-                        let shape_handle: Option<Collider> = match data.shape.as_str() {
-                            "cuboid" => Some(Collider::cuboid(data.scale[0],data.scale[1],data.scale[2])),
+        // place train on its rail, facing the way it points
+        let rail = data.rail.as_ref().and_then(|segment_name| {
+            rails_state.segments.get(segment_name).map(|segment| {
+                let distance = segment.project(data.translation);
+                let (_point, tangent) = segment.point_at(distance);
+                RailPosition {
+                    segment: segment_name.clone(),
+                    distance,
+                    facing: if tangent.dot(-Vec3::Z) >= 0.0 { 1.0 } else { -1.0 },
+                }
+            })
+        });
+        let train_name = data.animatable.clone().unwrap_or_default();
+
+        // leading prop first, then coupled cars
+        let car_datas = std::iter::once(WorldTrainCar { prop: data.prop.clone(), flipped: false })
+            .chain(data.cars.iter().cloned())
+            .collect::<Vec<WorldTrainCar>>();
+        let mut train_cars = car_datas.iter().map(|car_data| {
+            let world_handle: Option<Handle<WorldAsset>> = match car_data.prop.as_str() {
+                "denki_train" => Some(world_handles.denki_train.clone()),
+                _ => None
+            };
+            // coupler ends from the car collider extents, forward is -Z
+            let car_asset = world_assets.get(&world_handle.unwrap()).unwrap();
+            let (front, rear) = car_asset.colliders.iter().fold((0.0_f32, 0.0_f32), |(front, rear), data| {
+                (front.max(data.scale[2] - data.translation[2]), rear.max(data.translation[2] + data.scale[2]))
+            });
+            TrainCarState {
+                front: if car_data.flipped { rear } else { front },
+                rear: if car_data.flipped { front } else { rear },
+                flipped: car_data.flipped,
+                rail: rail.clone(),
+                ..default()
+            }
+        }).collect::<Vec<TrainCarState>>();
+        place_train_cars(&rails_state, &mut train_cars, 0);
+
+        let mut car_offset = 0.0;
+        for (car_idx, car_data) in car_datas.iter().enumerate() {
+            if car_idx > 0 {
+                car_offset += train_car_spacing(&train_cars[car_idx - 1], &train_cars[car_idx]);
+            }
+            let car_transform = if let Some(car_rail) = &train_cars[car_idx].rail {
+                let (point, tangent) = rails_state.point_at(car_rail);
+                let car_facing = if car_data.flipped { -car_rail.facing } else { car_rail.facing };
+                Transform::from_translation(point).looking_at(point + tangent * car_facing, Vec3::Y)
+            } else {
+                // free running cars line up behind the leading car
+                let mut car_transform = Transform::from_translation(data.translation + car_offset * Vec3::Z);
+                if car_data.flipped {
+                    car_transform.rotate_y(std::f32::consts::PI);
+                }
+                car_transform
+            };
+            let mut car_doors: Vec<String> = Vec::new();
+            let car_handle = Some(commands
+                .spawn_bundle(SpatialBundle::from_transform(car_transform))
+                    .insert(WorldEntity)
+                    // kinematic body carries riders and child colliders
+                    .insert(RigidBody::KinematicVelocityBased)
+                    .insert(Velocity::zero())
+                    .with_children(|parent| {
+                        let world_handle: Option<Handle<WorldAsset>> = match car_data.prop.as_str() {
+                            "denki_train" => Some(world_handles.denki_train.clone()),
                             _ => None
                         };
-                        if shape_handle.is_some() {
-                            parent
-                                    .spawn_bundle(SpatialBundle::from_transform(
-                                        Transform::from_translation(data.translation)))
-                                    .insert(shape_handle.unwrap())
-                                    .insert(CollisionGroups::new(0b0001, 0b0001))
-
-                                    .with_children(|parent| {
-                                        if settings.graphics_settings.render_mode.as_str() == "colliders" {
-                                            parent.spawn_bundle(PbrBundle {
-                                                mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
-                                                material: materials.add(Color::rgb(0.8, 0.7, 0.6).into()),
-                                                transform: Transform::from_scale(data.scale*2.0),
-                                                ..default()
-                                            });
-                                        }
-                                    })
-                                    ;
+                        let car_asset = world_assets.get(&world_handle.unwrap()).unwrap();
+                        // colliders
+                        for data in car_asset.colliders.iter() {
+                            let shape_handle: Option<Collider> = match data.shape.as_str() {
+                                "cuboid" => Some(Collider::cuboid(data.scale[0],data.scale[1],data.scale[2])),
+                                _ => None
+                            };
+                            if shape_handle.is_some() {
+                                parent
+                                        .spawn_bundle(SpatialBundle::from_transform(
+                                            Transform::from_translation(data.translation)))
+                                        .insert(shape_handle.unwrap())
+                                        .insert(CollisionGroups::new(0b0001, 0b0001))
+
+                                        .with_children(|parent| {
+                                            if settings.graphics_settings.render_mode.as_str() == "colliders" {
+                                                parent.spawn_bundle(PbrBundle {
+                                                    mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
+                                                    material: materials.add(Color::rgb(0.8, 0.7, 0.6).into()),
+                                                    transform: Transform::from_scale(data.scale*2.0),
+                                                    ..default()
+                                                });
+                                            }
+                                        })
+                                        ;
 
+                            }
                         }
-                    }
-                    // gltf props
-                    for data in train_asset.props.iter() {
-                        let mut prop_instance: Option<InstanceId> = None;
-                        let prop_handle: Option<Handle<Scene>> = match data.prop.as_str() {
-                            "denki_train" => Some(world_props.denki_train.clone()),
-                            _ => None
-                        };
-                        if prop_handle.is_some() {
-                            parent.spawn_bundle(SpatialBundle::from_transform(
-                                Transform::from_translation(data.translation)
-                            )).with_children(|parent2| {
-                                let parent = parent2.spawn_bundle(SpatialBundle::from_transform(
-                                    Transform::from_rotation(data.rotation)
-                                )).id();
-                                if settings.graphics_settings.render_mode.as_str() != "colliders" {
-                                    prop_instance = Some(scene_spawner.spawn_as_child(prop_handle.unwrap(), parent));
+                        // gltf props, animatable ones by name for lamp lookups
+                        let mut car_instances: HashMap<String, InstanceId> = HashMap::new();
+                        for data in car_asset.props.iter() {
+                            let mut prop_instance: Option<InstanceId> = None;
+                            let prop_handle: Option<Handle<Scene>> = match data.prop.as_str() {
+                                "denki_train" => Some(world_props.denki_train.clone()),
+                                _ => None
+                            };
+                            if prop_handle.is_some() {
+                                let mut prop_commands = parent.spawn_bundle(SpatialBundle::from_transform(
+                                    Transform::from_translation(data.translation)
+                                ));
+                                prop_commands.with_children(|parent2| {
+                                    let parent = parent2.spawn_bundle(SpatialBundle::from_transform(
+                                        Transform::from_rotation(data.rotation)
+                                    )).id();
+                                    if settings.graphics_settings.render_mode.as_str() != "colliders" {
+                                        prop_instance = Some(scene_spawner.spawn_as_child(prop_handle.clone().unwrap(), parent));
+                                    }
+                                });
+                                if let Some(auto_collider) = auto_collider(&data.auto_collider, prop_instance, prop_handle.clone(), data.rotation, None) {
+                                    prop_commands.insert(auto_collider);
                                 }
-                            });
+                            }
+                            if let Some((animatable, prop_instance)) = data.animatable.clone().zip(prop_instance) {
+                                car_instances.insert(animatable, prop_instance);
+                            }
                         }
-                    }
-                    // lights
-                    for data in train_asset.lights.iter() {
-                        let light_entity = if data.light_type == "spot" {
-                            parent.spawn_bundle(DirectionalLightBundle {
-                                transform: Transform::from_translation(data.translation),
-                                directional_light: DirectionalLight {
-                                    illuminance: data.watts,
-                                    shadows_enabled: true,
+                        // lights
+                        for data in car_asset.lights.iter() {
+                            let light_entity = if data.light_type == "spot" {
+                                parent.spawn_bundle(DirectionalLightBundle {
+                                    transform: Transform::from_translation(data.translation),
+                                    directional_light: DirectionalLight {
+                                        illuminance: data.watts,
+                                        shadows_enabled: true,
+                                        ..default()
+                                    },
                                     ..default()
-                                },
-                                ..default()
-                            }).id()
-                        } else {
-                            parent.spawn_bundle(PointLightBundle {
-                                transform: Transform::from_translation(data.translation),
-                                point_light: PointLight {
-                                    intensity: data.watts,
-                                    shadows_enabled: true,
+                                }).id()
+                            } else {
+                                parent.spawn_bundle(PointLightBundle {
+                                    transform: Transform::from_translation(data.translation),
+                                    point_light: PointLight {
+                                        intensity: data.watts,
+                                        shadows_enabled: true,
+                                        ..default()
+                                    },
                                     ..default()
-                                },
-                                ..default()
-                            }).id()
-                        };
-                        if data.animatable.is_some() {
-                            world_state.animatable_lights.insert(data.animatable.clone().unwrap(), light_entity);
+                                }).id()
+                            };
+                            // patterned lights without a name are keyed by entity
+                            if data.animatable.is_some() || data.behaviour.is_some() || data.emissive.is_some() || data.dusk {
+                                let name = data.animatable.clone().unwrap_or_else(|| format!("light.{}", light_entity.id()));
+                                let emissive_instance = data.emissive.as_ref()
+                                    .and_then(|emissive| car_instances.get(&emissive.prop).copied());
+                                world_state.animatable_lights.insert(name, light_state(data, light_entity, emissive_instance));
+                            }
                         }
-                    }
-                    // interactables
-                    for data in train_asset.interactables.iter() {
-                        if data.interaction.is_some() {
-                            let collider = Collider::ball(data.scale[0]);
-                            let cgroup = if data.interaction.as_ref().unwrap().interaction == "click" {
-                                CollisionGroups::new(0b0100, 0b0100)
-                            } else {
-                                CollisionGroups::new(0b0010, 0b0010)
+                        // doors, named per train car
+                        for data in car_asset.doors.iter() {
+                            let prop_handle: Option<Handle<Scene>> = match data.prop.as_str() {
+                                "door_blue" => Some(world_props.door_blue.clone()),
+                                _ => None
                             };
-                            let collider_ent_id = parent
-                                    .spawn_bundle(SpatialBundle::from_transform(
-                                        Transform::from_translation(data.translation)))
-                                    .insert(collider)
-                                    .insert(cgroup)
-                                    .insert(Sensor {})
-                                    .with_children(|parent| {
-                                        if settings.graphics_settings.render_mode.as_str() == "colliders" {
-                                            parent.spawn_bundle(PbrBundle {
-                                                mesh: meshes.add(Mesh::from(shape::UVSphere { radius: data.scale[0], ..Default::default() })),
-                                                material: materials.add(Color::rgb(0.0, 0.7, 0.6).into()),
-                                                ..default()
-                                            });
-                                        }
-                                    })
-                                    .id();
-                            // todo store interaction type, collider_ent_id, etc
-                            world_state.interactable_states.insert(collider_ent_id, InteractableState { interaction: data.interaction.clone().unwrap() });
-                        } else {
-                            println!("unknown interactable :: {:?}", data);
+                            let mut collider_entity: Option<Entity> = None;
+                            let mut prop_instance: Option<InstanceId> = None;
+                            if prop_handle.is_some() {
+                                let door_name = format!("{}.{}.{}", train_name, car_idx, data.name);
+                                let mut door_commands = parent.spawn_bundle(SpatialBundle::from_transform(
+                                    Transform::from_translation(data.translation)
+                                ));
+                                door_commands.with_children(|parent2| {
+                                    let parent = parent2.spawn_bundle(SpatialBundle::from_transform(
+                                        Transform::from_rotation(data.rotation)
+                                    )).id();
+                                    if settings.graphics_settings.render_mode.as_str() != "colliders" {
+                                        prop_instance = Some(scene_spawner.spawn_as_child(prop_handle.clone().unwrap(), parent));
+                                    }
+                                    if data.auto_collider.is_some() {
+                                        return;
+                                    }
+
+                                    let door_scale = data.scale * Vec3::new(0.8, 1.5, 0.05);
+                                    collider_entity = Some(parent2
+                                            .spawn_bundle(SpatialBundle::from_transform(
+                                                Transform::from_translation(door_scale[1] * Vec3::Y)
+                                                    .with_rotation(data.rotation)))
+                                            .insert(Collider::cuboid(door_scale[0],door_scale[1],door_scale[2]))
+                                            .insert(CollisionGroups::new(0b0001, 0b0001))
+                                            .with_children(|parent| {
+                                                if settings.graphics_settings.render_mode.as_str() == "colliders" {
+                                                    parent.spawn_bundle(PbrBundle {
+                                                        mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
+                                                        material: materials.add(Color::rgb(0.5, 0.1, 0.1).into()),
+                                                        transform: Transform::from_scale(door_scale*2.0),
+                                                        ..default()
+                                                    });
+                                                }
+                                            })
+                                            .id());
+                                });
+                                if let Some(auto_collider) = auto_collider(&data.auto_collider, prop_instance, prop_handle.clone(), data.rotation, Some(door_name.clone())) {
+                                    door_commands.insert(auto_collider);
+                                }
+                                let door_entity = door_commands.id();
+                                world_state.doors.insert(door_name.clone(), door_state(
+                                    data, &world_asset.feedback_sounds, Transform::from_translation(data.translation), Some(door_entity), collider_entity));
+                                car_doors.push(door_name);
+                            }
                         }
-                    }
-                })
-                .id());
-            if data.animatable.is_some() {
-                // store animation scene spawner reference
-                world_state.animatable_trains.insert(data.animatable.clone().unwrap(), WorldTrainState {
-                    parent_entity: train_handle,
-                    running: false,
-                });
-            }
+                        // interactables
+                        for data in car_asset.interactables.iter() {
+                            if data.interaction.is_some() {
+                                let collider = Collider::ball(data.scale[0]);
+                                let cgroup = if data.interaction.as_ref().unwrap().interaction == "click" {
+                                    CollisionGroups::new(0b0100, 0b0100)
+                                } else {
+                                    CollisionGroups::new(0b0010, 0b0010)
+                                };
+                                let collider_ent_id = parent
+                                        .spawn_bundle(SpatialBundle::from_transform(
+                                            Transform::from_translation(data.translation)))
+                                        .insert(collider)
+                                        .insert(cgroup)
+                                        .insert(Sensor {})
+                                        .with_children(|parent| {
+                                            if settings.graphics_settings.render_mode.as_str() == "colliders" {
+                                                parent.spawn_bundle(PbrBundle {
+                                                    mesh: meshes.add(Mesh::from(shape::UVSphere { radius: data.scale[0], ..Default::default() })),
+                                                    material: materials.add(Color::rgb(0.0, 0.7, 0.6).into()),
+                                                    ..default()
+                                                });
+                                            }
+                                        })
+                                        .id();
+                                // todo store interaction type, collider_ent_id, etc
+                                world_state.interactable_states.insert(collider_ent_id, InteractableState { interaction: data.interaction.clone().unwrap() });
+                            } else {
+                                println!("unknown interactable :: {:?}", data);
+                            }
+                        }
+                    })
+                    .id());
+            train_cars[car_idx].entity = car_handle;
+            train_cars[car_idx].doors = car_doors;
+        }
+
+        if data.animatable.is_some() {
+            // store animation scene spawner reference
+            world_state.animatable_trains.insert(data.animatable.clone().unwrap(), WorldTrainState {
+                parent_entity: train_cars[0].entity,
+                running: false,
+                dynamics: data.dynamics.clone(),
+                throttle: 0,
+                direction: 1.0,
+                braking: false,
+                speed: 0.0,
+                cars: train_cars,
+                autopilot: if data.timetable.is_empty() { None } else {
+                    Some(TrainAutopilot {
+                        timetable: data.timetable.clone(),
+                        next_stop: 0,
+                        dwell_rmn: 0.0,
+                    })
+                },
+            });
+        }
+    }
+}
+
+// scene handle for a world prop name
+fn prop_scene_handle(
+    prop: &str,
+    world_props: &WorldProps,
+    world01_props: &World01Props,
+    world03_props: &World03Props,
+) -> Option<Handle<Scene>> {
+    match prop {
+        "big_switch" => Some(world_props.big_switch.clone()),
+        "bottle_lightfuel" => Some(world_props.bottle_lightfuel.clone()),
+        "cardboard_closed" => Some(world01_props.cardboard_closed.clone()),
+        "cardboard_opened" => Some(world01_props.cardboard_opened.clone()),
+        "cardboard_tube" => Some(world01_props.cardboard_tube.clone()),
+        "city_fence" => Some(world_props.city_fence.clone()),
+        "diesel_generator" => Some(world_props.diesel_generator.clone()),
+        "door_blue" => Some(world_props.door_blue.clone()),
+        "fountain_round" => Some(world01_props.fountain_round.clone()),
+        "fuse_console" => Some(world_props.fuse_console.clone()),
+        "fuse_small" => Some(world_props.fuse_small.clone()),
+        "house_roof01" => Some(world01_props.house_roof01.clone()),
+        "house_woodside" => Some(world01_props.house_woodside.clone()),
+        "office_table" => Some(world01_props.office_desk01.clone()),
+        "office_desk" => Some(world01_props.office_desk02.clone()),
+        "office_chair" => Some(world01_props.office_chair.clone()),
+        "pallet" => Some(world01_props.pallet.clone()),
+        "rail_track" => Some(world_props.rail_track.clone()),
+        "train_wire" => Some(world_props.train_wire.clone()),
+        "tunnel_entrance" => Some(world_props.tunnel_entrance.clone()),
+        "world01_building01" => Some(world01_props.world01_building01.clone()),
+        "world01_generator_wire" => Some(world_props.world01_generator_wire.clone()),
+        "world01_ground01" => Some(world01_props.world01_ground01.clone()),
+        "world01_ground02" => Some(world_props.world01_ground02.clone()),
+        "world01_ground03" => Some(world_props.world01_ground03.clone()),
+        "refinery_column01" => Some(world03_props.refinery_column01.clone()),
+        "refinery_desalter" => Some(world03_props.refinery_desalter.clone()),
+        "refinery_scaffolding" => Some(world03_props.refinery_scaffolding.clone()),
+        "refinery_sphere" => Some(world03_props.refinery_sphere.clone()),
+        "refinery_tank01" => Some(world03_props.refinery_tank01.clone()),
+        "refinery_warmer" => Some(world03_props.refinery_warmer.clone()),
+        "world03_ground" => Some(world03_props.world03_ground.clone()),
+        "world03_pipes" => Some(world03_props.world03_pipes.clone()),
+        "world03_walkways" => Some(world03_props.world03_walkways.clone()),
+        _ => None
+    }
+}
+
+// auto collider request for a prop scene, read from the asset when not spawned
+fn auto_collider(
+    data: &Option<WorldAutoCollider>,
+    instance: Option<InstanceId>,
+    scene: Option<Handle<Scene>>,
+    rotation: Quat,
+    door: Option<String>,
+) -> Option<AutoCollider> {
+    let data = data.as_ref()?;
+    let source = match instance {
+        Some(instance) => AutoColliderSource::Instance(instance),
+        None => AutoColliderSource::Scene(scene?, Transform::from_rotation(rotation)),
+    };
+    Some(AutoCollider {
+        source,
+        shape: data.shape.clone(),
+        groups: CollisionGroups::new(data.memberships, data.filters),
+        door,
+    })
+}
+
+// door state with its hinge placed in the door parent space
+fn door_state(
+    data: &WorldDoor,
+    feedback_sounds: &WorldFeedbackSounds,
+    closed_transform: Transform,
+    parent_entity: Option<Entity>,
+    collider_entity: Option<Entity>,
+) -> DoorState {
+    // doors without their own sounds use the world feedback sounds
+    let mut behaviour = data.behaviour.clone();
+    behaviour.open_sound = behaviour.open_sound.or_else(|| feedback_sounds.door_open.clone());
+    behaviour.close_sound = behaviour.close_sound.or_else(|| feedback_sounds.door_close.clone());
+    behaviour.locked_sound = behaviour.locked_sound.or_else(|| feedback_sounds.door_locked.clone());
+    DoorState {
+        parent_entity,
+        collider_entity,
+        open: false,
+        closed_transform,
+        hinge_pivot: data.rotation * (data.hinge.pivot * data.scale),
+        hinge_axis: (data.rotation * data.hinge.axis).normalize_or_zero(),
+        swing_angle: data.hinge.angle.to_radians(),
+        swing_speed: data.hinge.speed.to_radians(),
+        swing: 0.0,
+        locked: data.behaviour.locked,
+        behaviour,
+        open_side: data.rotation * Vec3::Z,
+        close_rmn: 0.0,
     }
 }
 
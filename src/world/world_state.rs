@@ -1,7 +1,10 @@
-use crate::world::{AnimatableStatePlugin,DoorStatePlugin,InteractableStatePlugin,
-    InventoryStatePlugin,LightsStatePlugin,SoundsStatePlugin,TrainsStatePlugin,
-    WorldFlagsStatePlugin,WorldInteraction};
+use crate::world::{AnimatableStatePlugin,CollidersStatePlugin,DoorStatePlugin,EchoesStatePlugin,EnvironmentStatePlugin,
+    InteractableStatePlugin,InventoryStatePlugin,LightsStatePlugin,MixerStatePlugin,MoversStatePlugin,MusicStatePlugin,
+    PlatformsStatePlugin,RailPosition,RailsStatePlugin,SoundsStatePlugin,TrainsStatePlugin,
+    WorldEchoes,WorldEchoZone,WorldFeedbackSounds,WorldFlagsStatePlugin,WorldFootsteps,WorldMusic,WorldDoorBehaviour,WorldPowerNetwork,WorldInteraction,WorldSoundSpatial,WorldTimetableStop,WorldTraversal,WorldTrainDynamics};
 use bevy::prelude::*;
+use bevy::scene::InstanceId;
+use rand::rngs::StdRng;
 use std::collections::HashMap;
 
 // todo break apart into modules, no need for unified world state
@@ -10,10 +13,17 @@ pub struct WorldState {
     pub active_world: String,
     pub interactable_states: HashMap<Entity, InteractableState>,
     pub animatables: HashMap<String, AnimatableState>,
-    pub animatable_lights: HashMap<String, Entity>,
+    pub animatable_lights: HashMap<String, WorldLightState>,
     pub animatable_sounds: HashMap<String, WorldSoundState>,
     pub animatable_trains: HashMap<String, WorldTrainState>,
     pub doors: HashMap<String, DoorState>,
+    pub feedback_sounds: WorldFeedbackSounds,
+    pub music: WorldMusic,
+    pub footsteps: WorldFootsteps,
+    pub echoes: WorldEchoes,
+    pub echo_zones: Vec<WorldEchoZone>,
+    pub traversals: Vec<WorldTraversal>,
+    pub power_networks: Vec<WorldPowerNetwork>,
     pub active_train: Option<String>,
 }
 
@@ -28,26 +38,140 @@ pub struct AnimatableState {
     pub clips: Vec<Handle<AnimationClip>>,
 }
 
+#[derive(Clone, Debug, Default)]
+pub struct WorldLightState {
+    pub entity: Option<Entity>,
+    pub on: bool,
+    // full intensity, watts for point and spot lights, lux for directional
+    pub intensity: f32,
+    pub color: Color,
+    // share of intensity lit, fading towards fade_target at fade_rate per second
+    pub level: f32,
+    pub fade_target: f32,
+    pub fade_rate: f32,
+    pub behaviour: Option<LightBehaviourState>,
+    pub emissive: Option<LightEmissiveState>,
+    pub dusk: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct LightEmissiveState {
+    pub instance: Option<InstanceId>,
+    pub node: Option<String>,
+    pub material: Option<String>,
+    pub strength: f32,
+    // per light copies of the lamp materials, filled once the scene is ready
+    pub materials: Vec<Handle<StandardMaterial>>,
+    pub resolved: bool,
+    // last written emissive
+    pub color: Color,
+}
+
+#[derive(Clone, Debug)]
+pub struct LightBehaviourState {
+    pub pattern: String,
+    pub rate: f32,
+    pub min: f32,
+    pub flag: Option<String>,
+    pub power: Option<String>,
+    pub rng: StdRng,
+    // set each frame from the pattern flag and the power network
+    pub active: bool,
+    pub powered: bool,
+    pub overloaded: bool,
+    // cycles through the pattern, 0.0 to 1.0
+    pub phase: f32,
+    // seconds left on the current random step
+    pub hold: f32,
+    // share of intensity lit, eased towards target for brown_out
+    pub level: f32,
+    pub target: f32,
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct WorldSoundState {
     pub sound: String,
     pub position: Vec3,
     pub panning: f32,
     pub volume: f32,
+    pub playback_rate: f32,
     pub paused: bool,
+    // mixer bus the channel plays through
+    pub bus: String,
+    // positional sounds, None plays flat
+    pub spatial: Option<WorldSoundSpatial>,
+    // entity carrying the sound, position follows it
+    pub emitter: Option<Entity>,
+    // volume and rate last sent to the channel after attenuation and doppler
+    pub output_volume: f32,
+    pub output_rate: f32,
+    // listener distance last frame for doppler
+    pub distance: Option<f32>,
+    // walls between listener and emitter, eased into occlusion 0.0 to 1.0
+    pub occluded: bool,
+    pub occlusion: f32,
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct WorldTrainState {
     pub parent_entity: Option<Entity>,
     pub running: bool,
+    pub dynamics: WorldTrainDynamics,
+    // throttle notch, 0 to dynamics.max_notch
+    pub throttle: i32,
+    // reverser, 1.0 forward or -1.0 reverse
+    pub direction: f32,
+    pub braking: bool,
+    // signed speed along the train forward axis
+    pub speed: f32,
+    // coupled cars front to back, the first carries parent_entity
+    pub cars: Vec<TrainCarState>,
+    pub autopilot: Option<TrainAutopilot>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TrainCarState {
+    pub entity: Option<Entity>,
+    // distances from the car origin to its coupler ends along the train forward
+    pub front: f32,
+    pub rear: f32,
+    // car faces against the train forward
+    pub flipped: bool,
+    // position on the rail network, free running if none
+    pub rail: Option<RailPosition>,
+    pub doors: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TrainAutopilot {
+    pub timetable: Vec<WorldTimetableStop>,
+    pub next_stop: usize,
+    // seconds left standing at the current station
+    pub dwell_rmn: f32,
 }
 
 
 #[derive(Debug, Default)]
 pub struct DoorState {
     pub parent_entity: Option<Entity>,
+    pub collider_entity: Option<Entity>,
     pub open: bool,
+    // pose of the closed door in its parent space
+    pub closed_transform: Transform,
+    // hinge relative to the closed door origin, in parent space
+    pub hinge_pivot: Vec3,
+    pub hinge_axis: Vec3,
+    // open angle and swing speed, in radians
+    pub swing_angle: f32,
+    pub swing_speed: f32,
+    // current swing, 0.0 when closed
+    pub swing: f32,
+    pub locked: bool,
+    pub behaviour: WorldDoorBehaviour,
+    // side a one-way door opens from, in parent space
+    pub open_side: Vec3,
+    // seconds left before an auto-closing door closes
+    pub close_rmn: f32,
 }
 
 pub struct WorldStatePlugin;
@@ -56,10 +180,18 @@ impl Plugin for WorldStatePlugin {
     fn build(&self, app: &mut App) {
         app
         .add_plugin(AnimatableStatePlugin)
+        .add_plugin(CollidersStatePlugin)
         .add_plugin(DoorStatePlugin)
+        .add_plugin(EchoesStatePlugin)
+        .add_plugin(EnvironmentStatePlugin)
         .add_plugin(InteractableStatePlugin)
         .add_plugin(InventoryStatePlugin)
         .add_plugin(LightsStatePlugin)
+        .add_plugin(MixerStatePlugin)
+        .add_plugin(MoversStatePlugin)
+        .add_plugin(MusicStatePlugin)
+        .add_plugin(PlatformsStatePlugin)
+        .add_plugin(RailsStatePlugin)
         .add_plugin(SoundsStatePlugin)
         .add_plugin(TrainsStatePlugin)
         .add_plugin(WorldFlagsStatePlugin)